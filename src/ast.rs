@@ -1,4 +1,21 @@
-use crate::scanner::{Token, TokenType, Value};
+use crate::scanner::{Token, Value};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Uint,
+    Long,
+    Float,
+    Bool,
+    String,
+    Void,
+    Array(Box<Type>), //pointer to [length][padding][elements]
+    List(Box<Type>), //pointer to [length][capacity][pointer to elements]
+    Struct(String), //pointer to the fields, in declaration order and aligned to their size
+    Enum(String), //i32 discriminant, the index of the variant
+    Func(Vec<Type>, Box<Type>), //pointer to [table slot][environment], the function takes the environment as its last parameter
+    Tuple(Vec<Type>), //several values on the wasm stack, only returned by functions and destructured right away
+}
 
 #[derive(Clone, Debug)]
 pub enum Expr {
@@ -21,7 +38,6 @@ pub enum Expr {
     Grouping(Box<Expr>), // "(" expression ")"
     Literal {
         val: Value,
-        line: usize,
     },
     Unary {
         operator: Token,
@@ -32,6 +48,55 @@ pub enum Expr {
         name: Token,
         line: usize
     },
+    Index {
+        array: Box<Expr>,
+        index: Box<Expr>,
+        line: usize,
+    }, //array[index]
+    SetIndex {
+        array: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        operator: Option<Token>,
+        line: usize,
+    }, //array[index] = value, the operator of array[index] += value
+    NewArray {
+        t: Type,
+        size: Box<Expr>,
+        line: usize,
+    }, //int[size] declarations allocate the array
+    Get {
+        object: Box<Expr>,
+        name: Token,
+        line: usize,
+    }, //object.field
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+        operator: Option<Token>,
+        line: usize,
+    }, //object.field = value, the operator of object.field += value
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+        line: usize,
+    }, //condition ? then_branch : else_branch
+    Cast {
+        t: Type,
+        value: Box<Expr>,
+        line: usize,
+    }, //float(i), int(f), bool(n)...
+    Lambda {
+        params: Vec<(Type, Token)>,
+        body: Box<Expr>,
+        line: usize,
+    }, //(int x) => x * k, captures the local variables it uses
+    Tuple {
+        values: Vec<Expr>,
+        line: usize,
+    }, //(a, b), the value of a function returning a tuple
 }
 
 #[derive(Clone, Debug)]
@@ -42,7 +107,7 @@ pub enum Stmt {
     },
     Block {
         stmts: Vec<Stmt>,
-        vars: Vec<TokenType>,
+        vars: Vec<Type>,
     },
     Expression(Expr),
     If {
@@ -54,25 +119,87 @@ pub enum Stmt {
     Var {
         name: Token,
         value: Option<Expr>,
-        t: TokenType,
+        t: Type,
+        line: usize,
+    },
+    Destructure {
+        targets: Vec<(Type, Token)>,
+        value: Expr,
+        line: usize,
+    }, //int q, int r = divmod(a, b);
+    Global {
+        name: Token,
+        value: Option<Expr>,
+        t: Type,
+        exported: bool,
         line: usize,
     },
+    Const {
+        name: Token,
+        value: Expr,
+        t: Type,
+        line: usize,
+    }, //replaced by its value at every use
     While {
         condition: Expr,
         block: Box<Stmt>,
+        label: Option<String>,
+        line: usize,
+    },
+    For {
+        init: Option<Box<Stmt>>,
+        condition: Expr,
+        increment: Option<Expr>,
+        block: Box<Stmt>,
+        label: Option<String>,
         line: usize,
     },
     Fn {
         name: String,
-        params: Vec<(TokenType, Token)>,
+        params: Vec<(Type, Token)>,
         body: Box<Stmt>,
-        return_type: TokenType,
+        return_type: Type,
+        line: usize,
+    },
+    Extern {
+        module: String,
+        name: String,
+        params: Vec<(Type, Token)>,
+        return_type: Type,
+        line: usize,
+    }, //a function imported from js
+    Struct {
+        name: String,
+        fields: Vec<(Type, Token)>,
+        line: usize,
+    },
+    Enum {
+        name: String,
+        variants: Vec<Token>,
+        line: usize,
+    },
+    Match {
+        value: Expr,
+        arms: Vec<(Option<Token>, Stmt)>, //variant (None is the _ wildcard), block
+        line: usize,
+    },
+    Switch {
+        value: Expr,
+        cases: Vec<(Vec<Expr>, Stmt)>, //values, block
+        default: Option<Box<Stmt>>,
         line: usize,
     },
     Return {
         returnee: Expr,
-        return_type: TokenType,
+        return_type: Type,
+        line: usize,
+    },
+    Break {
+        label: Option<String>,
+        line: usize,
+    },
+    Continue {
+        label: Option<String>,
         line: usize,
     },
-    Break (i32),
 }
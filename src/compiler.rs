@@ -1,175 +1,193 @@
-use std::collections::HashMap;
-use wasm_encoder::{BlockType, CodeSection, ConstExpr, DataSection, EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction, MemoryType, Module, TypeSection, ValType};
+use std::collections::{HashMap, HashSet};
+use wasm_encoder::{BlockType, CodeSection, ConstExpr, DataSection, ElementSection, Elements, Encode, EntityType, ExportKind, ExportSection, Function, FunctionSection, GlobalSection, GlobalType, ImportSection, Instruction, MemArg, MemoryType, Module, RefType, TableSection, TableType, TypeSection, ValType};
 use std::fs;
-use crate::{ast::Expr, scanner::{Value, TokenType}};
-use crate::ast::Stmt;
+use crate::{ast::Expr, scanner::{Token, Value, TokenType}};
+use crate::ast::{Stmt, Type};
 use crate::errors::KeyScriptError;
+use crate::runtime::{mem, Helper};
 use std::fs::metadata;
 use std::io::Write;
 use colored::Colorize;
 
 //REMINDER of wasm structure:
-//first types
+//first types (one for every distinct signature)
 //then functions and indexes
+//then the table of the functions that are called through function values
 //then exports
 //then code
 //booleans are stored in i32!!
-//Strings are stored in i32 as well!! (offset)
+//Strings are stored in i32 as well!! (pointer to [length][bytes] in memory)
+
+type Fields = Vec<(String, Type, u64)>; //the name, type and offset of every field of a struct
 
 pub struct Compiler {
     module: Module,
-    vars: HashMap<String, (u32, TokenType)>,
-    vars_count: u32,
-    funcs: HashMap<String, (u32, TokenType)>,
-    vars1: Vec<TokenType>,
+    scopes: Vec<HashMap<String, (u32, Type)>>, //local index, type; the first scope holds the parameters, innermost last
+    locals_count: u32, //locals of the current function declared so far, parameters included
+    declared: HashSet<String>, //every variable declared in the current function, for use before declaration errors
+    ended: HashSet<String>, //variables of the blocks that have ended
+    funcs: HashMap<String, (u32, Vec<Type>, Type)>, //index, params, return type
+    vars1: Vec<Type>,
     code: Vec<Stmt>,
     path: String,
-    strings: Vec<u8>,
-    offsets: HashMap<i32, i32>, //offset, length
-    string_vars: HashMap<String, i32>, //name, offset
+    strings: Vec<u8>, //static data, copied into memory at offset 0
+    interned: HashMap<String, i32>, //string literal, offset
     kys_funcs: Vec<Stmt>,
+    externs: Vec<Stmt>, //imported after console.log, before main
+    structs: HashMap<String, (Fields, i32)>, //fields, size
+    struct_names: Vec<String>, //in the order of their constructors
+    enums: HashMap<String, Vec<String>>, //variants, the discriminant is the index
+    globals: HashMap<String, (u32, Type)>,
+    kys_globals: Vec<(String, Type, Option<Value>, bool)>, //name, type, constant initial value, exported
+    helpers: Vec<Helper>,
+    helper_base: u32,
+    signatures: Vec<(Vec<ValType>, Vec<ValType>)>, //params, results; the position is the type index
+    table: Vec<(Function, u32)>, //the functions called through function values (after the helpers) and their type index
+    func_values: HashMap<u32, i32>, //function index, offset of its static [table slot][environment] record
+    uses_table: bool, //a call_indirect was emitted, the table is needed even when no function value was created
+    control: Vec<Control>, //the blocks, loops and ifs around the current statement, innermost last
+    scratch: u32, //an i32 local after the declared locals of every function, for temporary values
     js: bool,
-    filename: String,
+    filename: String, //the file of the code being compiled, for errors
+    main_filename: String,
+    origins: HashMap<String, String>, //function or type name, the file declaring it
+}
+
+//what branching to an enclosing wasm block/loop/if does
+#[derive(Clone, PartialEq)]
+enum Control {
+    Other,
+    Break(Option<String>), //leaves the loop with this label
+    Continue(Option<String>), //skips to the next iteration of the loop with this label
 }
 
 impl Compiler {
-    pub fn new(code: Vec<Stmt>, vars1: Vec<TokenType>, filename: &str, js: bool) -> Compiler {
-        Compiler {
+    pub fn new(code: Vec<Stmt>, vars1: Vec<Type>, filename: &str, origins: HashMap<String, String>, js: bool) -> Compiler {
+        let mut comp = Compiler {
             module: Module::new(),
-            vars: HashMap::new(),
-            vars_count: 0,
+            scopes: Vec::new(),
+            locals_count: 0,
+            declared: HashSet::new(),
+            ended: HashSet::new(),
             funcs: HashMap::new(),
             vars1,
             code,
             path: filename.to_string().replace(".kys", ".wasm"),
             strings: Vec::new(),
-            offsets: HashMap::new(),
-            string_vars: HashMap::new(),
+            interned: HashMap::new(),
             kys_funcs: Vec::new(),
+            externs: Vec::new(),
+            structs: HashMap::new(),
+            struct_names: Vec::new(),
+            enums: HashMap::new(),
+            globals: HashMap::new(),
+            kys_globals: Vec::new(),
+            helpers: Vec::new(),
+            helper_base: 0,
+            signatures: Vec::new(),
+            table: Vec::new(),
+            func_values: HashMap::new(),
+            uses_table: false,
+            control: Vec::new(),
+            scratch: 0,
             js,
             filename: filename.to_string(),
-        }
+            main_filename: filename.to_string(),
+            origins,
+        };
+        comp.make_string(String::new()); //offset 0 is the empty string, the default value of string variables
+        comp
     }
 
     pub fn compile(&mut self, is_wat: bool) {
-        while let Some(stmt) = self.code.first() {
-            match stmt {
-                Stmt::Fn {..} => {
-                    self.kys_funcs.push(self.code.remove(0));
-                }
+        let mut structs = vec![];
+        let mut enums = vec![];
+        loop {
+            match self.code.first() {
+                Some(Stmt::Fn {..}) => self.kys_funcs.push(self.code.remove(0)),
+                Some(Stmt::Extern {..}) => self.externs.push(self.code.remove(0)),
+                Some(Stmt::Struct {..}) => structs.push(self.code.remove(0)),
+                Some(Stmt::Enum {..}) => enums.push(self.code.remove(0)),
                 _ => break,
             }
         }
-        let mut counter = 2;
-        for i in self.kys_funcs.iter() {
+        //imported functions come first: console.log is 0, then the extern functions, then main
+        let mut counter = 1;
+        for i in self.externs.clone() {
+            if let Stmt::Extern {module, name, params, return_type, line} = i {
+                self.set_origin(&name);
+                self.declare_extern(&module, &name, &params, &return_type, line);
+                self.funcs.insert(name, (counter, params.into_iter().map(|p| p.0).collect(), return_type));
+                counter += 1;
+            }
+        }
+        let main_index = counter;
+        counter += 1;
+        for i in self.kys_funcs.clone().iter() {
             match i {
                 Stmt::Fn {
                     name,
+                    params,
                     return_type,
+                    line,
                     ..
                 } => {
-                    self.funcs.insert(name.clone(), (counter, return_type.clone()));
+                    self.set_origin(name);
+                    if self.funcs.contains_key(name) {
+                        self.error(format!("function \"{}\" already declared", name).as_str(), Some(*line));
+                    }
+                    self.funcs.insert(name.clone(), (counter, params.iter().map(|p| p.0.clone()).collect(), return_type.clone()));
                     counter += 1;
                 }
                 _ => self.error("failed to compile the functions", None),
             }
         }
-
-        let mut types = TypeSection::new();
-        let params = vec![ValType::I32, ValType::I32];
-        let results = vec![];
-        types.function(params, results); //print, 0
-        let params = vec![];
-        let results = vec![];
-        types.function(params, results); //main function, 1
-        for i in self.kys_funcs.iter() {
-            if let Stmt::Fn {
-                params,
-                return_type,
-                ..
-            } = i {
-                let mut params1 = vec![];
-                for param in params {
-                    self.strings.extend_from_slice(param.1.literal.clone().unwrap().as_str().as_bytes());
-                    self.offsets.insert(self.strings.len() as i32 - param.1.literal.clone().unwrap().as_str().len() as i32, param.1.literal.clone().unwrap().as_str().len() as i32);
-                    self.string_vars.insert(param.1.literal.clone().unwrap().as_str().to_string(), self.strings.len() as i32 - param.1.literal.clone().unwrap().as_str().len() as i32);
-                    params1.push(match param.0 {
-                        TokenType::Int => ValType::I32,
-                        TokenType::Float => ValType::F64,
-                        TokenType::Bool => ValType::I32,
-                        TokenType::String => ValType::I32,
-                        _ => {self.error("function cannot have a string index as a variable", None); std::process::exit(0);},
-                    });
-                }
-                let mut results1 = vec![];
-                match return_type {
-                    TokenType::Int => results1.push(ValType::I32),
-                    TokenType::Float => results1.push(ValType::F64),
-                    TokenType::Bool => results1.push(ValType::I32),
-                    TokenType::String => results1.push(ValType::I32),
-                    TokenType::Void => {},
-                    _ => {self.error("function cannot have a string index as a variable", None); std::process::exit(0);},
+        for i in structs {
+            if let Stmt::Struct {name, fields, line} = i {
+                self.set_origin(&name);
+                if self.funcs.contains_key(&name) {
+                    self.error(format!("struct \"{}\" has the same name as a function", name).as_str(), Some(line));
                 }
-                types.function(params1, results1);
+                //every struct has a constructor function taking its fields: Point(x, y)
+                self.funcs.insert(name.clone(), (counter, fields.iter().map(|f| f.0.clone()).collect(), Type::Struct(name.clone())));
+                self.declare_struct(name, fields);
+                counter += 1;
             }
         }
-        self.module.section(&types);
-
-        let mut imports = ImportSection::new();
-        imports.import("wasm", "memory", EntityType::Memory(MemoryType{
-            minimum: 1,
-            maximum: None,
-            memory64: false,
-            shared: false,
-        }));
-        imports.import("console", "log", EntityType::Function(0));
-        self.module.section(&imports);
-
-        let mut functions = FunctionSection::new();
-        let type_index = 1;
-        functions.function(type_index);
-        let mut counter = 2;
-        for _ in self.kys_funcs.iter() {
-            functions.function(counter);
-            counter += 1;
+        for i in enums {
+            if let Stmt::Enum {name, variants, line} = i {
+                self.set_origin(&name);
+                if self.funcs.contains_key(&name) {
+                    self.error(format!("enum \"{}\" has the same name as a function", name).as_str(), Some(line));
+                }
+                self.enums.insert(name, variants.into_iter().map(|v| v.literal.unwrap().as_str()).collect());
+            }
         }
-        self.module.section(&functions);
-
-        let mut func_names: Vec<String> = vec!["main".to_string()];
-        let mut exports = ExportSection::new();
-        exports.export("main", ExportKind::Func, 1);
-        counter = 2;
-        for i in self.kys_funcs.iter() {
-            if let Stmt::Fn {
-                name,
-                ..
-            } = i {
-                func_names.push(name.clone());
-                exports.export(name.as_str(), ExportKind::Func, counter);
-                counter += 1;
+        self.helper_base = counter; //runtime helpers come after the keyscript functions and constructors
+        self.filename = self.main_filename.clone();
+        for stmt in self.code.clone() {
+            if let Stmt::Global {name, value, t, exported, line} = stmt {
+                self.declare_global(name, value, t, exported, line);
             }
         }
-        self.module.section(&exports);
 
         let mut codes = CodeSection::new();
         let mut locals = vec![];
         for var in &self.vars1 {
-            match var {
-                TokenType::Int => locals.push((1,ValType::I32)),
-                TokenType::Float => locals.push((1,ValType::F64)),
-                TokenType::Bool => locals.push((1,ValType::I32)),
-                TokenType::String => locals.push((1,ValType::I32)),
-                _ => self.error("undefined param type", None),
-            }
+            locals.push((1, self.val_type(var, None)));
         }
+        locals.push((1, ValType::I32));
+        self.scratch = self.vars1.len() as u32;
         let mut f = Function::new(locals);
+        self.begin_function(&[], &self.code.clone());
         for stmt in self.code.clone() {
             self.compile_stmt(&mut f, stmt);
         }
         f.instruction(&Instruction::End);
         codes.function(&f);
         for i in self.kys_funcs.clone() {
-            if let Stmt::Fn {body, params, line, ..} = i {
+            if let Stmt::Fn {name, body, params, line, ..} = i {
+                self.set_origin(&name);
                 let mut locals = vec![];
                 match *body.clone() {
                     Stmt::Block {
@@ -177,36 +195,173 @@ impl Compiler {
                         ..
                     } => {
                         for var in vars {
-                            match var {
-                                TokenType::Int => locals.push((1, ValType::I32)),
-                                TokenType::Float => locals.push((1, ValType::F64)),
-                                TokenType::Bool => locals.push((1, ValType::I32)),
-                                TokenType::String => locals.push((1, ValType::I32)),
-                                _ => self.error("undefined param type in function", Some(line)),
-                            }
+                            locals.push((1, self.val_type(&var, Some(line))));
                         }
                     }
                     _ => self.error("function must contain a block", Some(line)),
                 }
+                self.scratch = (params.len() + locals.len()) as u32;
+                locals.push((1, ValType::I32));
                 let mut f = Function::new(locals);
-                for param in params {
-                    self.vars.insert(match param.1.literal.clone().unwrap() {
-                        Value::String(s) => s,
-                        _ => {self.error("param name must be a string", Some(line)); std::process::exit(0);},
-                    }, (self.vars_count, param.0));
-                    self.vars_count += 1;
-                }
-                self.compile_stmt(&mut f, *body.clone());
+                let stmts = match *body {
+                    Stmt::Block {stmts, ..} => stmts,
+                    _ => vec![],
+                };
+                //the body shares the scope of the parameters, so it cannot redeclare them
+                self.begin_function(&params, &stmts);
+                for stmt in stmts {
+                    self.compile_stmt(&mut f, stmt);
+                }
                 f.instruction(&Instruction::End);
                 codes.function(&f);
             }
         }
+        for name in self.struct_names.clone() {
+            let f = self.constructor(&name);
+            codes.function(&f);
+        }
+        //compiling a helper can pull in more helpers, so the list can grow while iterating
+        let mut i = 0;
+        while i < self.helpers.len() {
+            let helper = self.helpers[i];
+            let f = helper.compile(self);
+            codes.function(&f);
+            i += 1;
+        }
+
+        for (f, _) in &self.table {
+            codes.function(f);
+        }
+
+        //the type of every function in function index order: print, externs, main, functions, constructors, helpers
+        let mut func_types = vec![self.signature(vec![ValType::I32, ValType::I32], vec![])];
+        for i in self.externs.clone() {
+            if let Stmt::Extern {params, return_type, ..} = i {
+                func_types.push(self.fn_signature(&params.into_iter().map(|p| p.0).collect::<Vec<Type>>(), &return_type, false));
+            }
+        }
+        func_types.push(self.signature(vec![], vec![]));
+        for i in self.kys_funcs.clone() {
+            if let Stmt::Fn {params, return_type, ..} = i {
+                func_types.push(self.fn_signature(&params.into_iter().map(|p| p.0).collect::<Vec<Type>>(), &return_type, false));
+            }
+        }
+        for name in self.struct_names.clone() {
+            let fields: Vec<Type> = self.structs[&name].0.iter().map(|f| f.1.clone()).collect();
+            func_types.push(self.fn_signature(&fields, &Type::Struct(name), false));
+        }
+        for helper in self.helpers.clone() {
+            func_types.push(self.signature(helper.params(), helper.results()));
+        }
+        let mut types = TypeSection::new();
+        for (params, results) in self.signatures.iter() {
+            types.function(params.clone(), results.clone());
+        }
+        self.module.section(&types);
+
+        let mut imports = ImportSection::new();
+        imports.import("wasm", "memory", EntityType::Memory(MemoryType{
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        }));
+        imports.import("console", "log", EntityType::Function(func_types[0]));
+        for i in self.externs.iter() {
+            if let Stmt::Extern {module, name, ..} = i {
+                imports.import(module, name, EntityType::Function(func_types[self.funcs[name].0 as usize]));
+            }
+        }
+        self.module.section(&imports);
+
+        let mut functions = FunctionSection::new();
+        for type_index in &func_types[main_index as usize..] {
+            functions.function(*type_index);
+        }
+        for (_, type_index) in &self.table {
+            functions.function(*type_index);
+        }
+        self.module.section(&functions);
+
+        //slot 0 of the table stays empty, calling an unassigned function value traps
+        let table_base = self.helper_base + self.helpers.len() as u32;
+        let slots: Vec<u32> = (table_base..table_base + self.table.len() as u32).collect();
+        if self.uses_table || !self.table.is_empty() {
+            let mut tables = TableSection::new();
+            tables.table(TableType {
+                element_type: RefType::FUNCREF,
+                minimum: slots.len() as u32 + 1,
+                maximum: Some(slots.len() as u32 + 1),
+            });
+            self.module.section(&tables);
+        }
+
+        let mut globals = GlobalSection::new();
+        let heap_start = (self.strings.len() as i32 + 7) & -8;
+        globals.global(GlobalType {
+            val_type: ValType::I32,
+            mutable: true,
+        }, &ConstExpr::i32_const(heap_start)); //the heap pointer, runtime::HEAP
+        for (_, t, value, _) in self.kys_globals.iter() {
+            let init = match value {
+                Some(Value::Int(n)) => ConstExpr::i32_const(*n),
+                Some(Value::Uint(n)) => ConstExpr::i32_const(*n as i32),
+                Some(Value::Long(n)) => ConstExpr::i64_const(*n),
+                Some(Value::Float(n)) => ConstExpr::f64_const(*n),
+                Some(Value::Bool(b)) => ConstExpr::i32_const(*b as i32),
+                Some(Value::String(s)) => ConstExpr::i32_const(self.interned[s]),
+                //initialized by main
+                None => match self.val_type(t, None) {
+                    ValType::F64 => ConstExpr::f64_const(0.0),
+                    ValType::I64 => ConstExpr::i64_const(0),
+                    _ => ConstExpr::i32_const(0),
+                },
+            };
+            globals.global(GlobalType {
+                val_type: self.val_type(t, None),
+                mutable: true,
+            }, &init);
+        }
+        self.module.section(&globals);
+
+        let mut func_names: Vec<(String, String)> = vec![("main".to_string(), Self::js_binding("main", &[], &Type::Void))]; //name, js binding
+        let mut exports = ExportSection::new();
+        exports.export("main", ExportKind::Func, main_index);
+        counter = main_index + 1;
+        for i in self.kys_funcs.iter() {
+            if let Stmt::Fn {
+                name,
+                params,
+                return_type,
+                ..
+            } = i {
+                //only the functions of the main file are exported, the ones of imported files are file.function
+                if !name.contains('.') {
+                    func_names.push((name.clone(), Self::js_binding(name, params, return_type)));
+                    exports.export(name.as_str(), ExportKind::Func, counter);
+                }
+                counter += 1;
+            }
+        }
+        let mut global_names: Vec<String> = vec![];
+        for (name, _, _, exported) in self.kys_globals.iter() {
+            if *exported {
+                global_names.push(name.clone());
+                exports.export(name.as_str(), ExportKind::Global, self.globals[name].0);
+            }
+        }
+        self.module.section(&exports);
+
+        if !self.table.is_empty() {
+            let mut elements = ElementSection::new();
+            elements.active(None, &ConstExpr::i32_const(1), Elements::Functions(&slots));
+            self.module.section(&elements);
+        }
+
         self.module.section(&codes);
 
         let mut data = DataSection::new();
-        for (&offset, length) in &self.offsets {
-            data.active(0, &ConstExpr::i32_const(offset), self.strings[offset as usize..(offset + length) as usize].into_iter().copied());
-        }
+        data.active(0, &ConstExpr::i32_const(0), self.strings.iter().copied());
         self.module.section(&data);
 
         let wasm_bytes = self.module.clone().finish();
@@ -215,7 +370,7 @@ impl Compiler {
         }
         fs::write(&self.path, &wasm_bytes).expect("Failed to write Wasm to file");
         if is_wat {
-            fs::write(&self.path.replace(".wasm", ".wat"), wasmprinter::print_file(&self.path).unwrap()).expect("Failed to write Wat to file");
+            fs::write(self.path.replace(".wasm", ".wat"), wasmprinter::print_file(&self.path).unwrap()).expect("Failed to write Wat to file");
         }
         if self.js {
             let name = self.path.replace(".wasm", ".html");
@@ -240,17 +395,31 @@ impl Compiler {
             log: function (offset, length) {
                 console.log(new TextDecoder('utf8').decode(new Uint8Array(imports.wasm.memory.buffer, offset, length)));
             }
-        }
-    };
-"#.to_string();
+        },"#.to_string();
+                html_code.push_str(&self.extern_stubs());
+                html_code.push_str("\n    };\n");
+                if self.externs.iter().any(|i| matches!(i, Stmt::Extern {params, ..} if params.iter().any(|p| p.0 == Type::String))) {
+                    //strings are pointers to [length][bytes]
+                    html_code.push_str(r#"    function kys_string(ptr) {
+        const length = new DataView(imports.wasm.memory.buffer).getUint32(ptr, true);
+        return new TextDecoder('utf8').decode(new Uint8Array(imports.wasm.memory.buffer, ptr + 4, length));
+    }
+"#);
+                }
                 html_code.push_str(r#"    function null_func() {
         console.log("ERROR! KeyScript file not loaded yet!");
     }
 "#);
                 html_code.push_str("    //the keyscript functions: ");
-                for i in func_names.clone() {
+                for (i, _) in func_names.clone() {
                     html_code.push_str(format!("\n    let {}_func = null_func;", i).as_str());
                 }
+                if !global_names.is_empty() {
+                    html_code.push_str("\n    //the exported globals, use .value to read or write them: ");
+                    for i in global_names.clone() {
+                        html_code.push_str(format!("\n    let {}_global = null;", i).as_str());
+                    }
+                }
                 html_code.push_str(r#"
     fetch('"#);
                 html_code.push_str(self.path.as_str());
@@ -260,8 +429,11 @@ impl Compiler {
             return WebAssembly.instantiate(bytes, imports)
         })
         .then(result => {"#);
-                for i in func_names {
-                    html_code.push_str(format!("\n            {}_func = result.instance.exports.{};", i, i).as_str());
+                for (i, binding) in func_names {
+                    html_code.push_str(format!("\n            {}_func = {};", i, binding).as_str());
+                }
+                for i in global_names {
+                    html_code.push_str(format!("\n            {}_global = result.instance.exports.{};", i, i).as_str());
                 }
 
                 html_code.push_str(r#"
@@ -281,25 +453,113 @@ impl Compiler {
         }
     }
 
+    fn declare_extern(&self, module: &str, name: &str, params: &[(Type, Token)], return_type: &Type, line: usize) {
+        if self.funcs.contains_key(name) {
+            self.error(format!("function \"{}\" already declared", name).as_str(), Some(line));
+        }
+        if module == "wasm" || (module == "console" && name == "log") {
+            self.error(format!("\"{}.{}\" is reserved, use another module name", module, name).as_str(), Some(line));
+        }
+        //strings are passed as pointers that js can read, but js cannot allocate one to return
+        for (t, param) in params {
+            if !matches!(t, Type::Int | Type::Uint | Type::Long | Type::Float | Type::Bool | Type::String) {
+                self.error(format!("extern parameter \"{}\" must be an int, uint, long, float, bool or string, got {:?}", param.literal.clone().unwrap().as_str(), t).as_str(), Some(line));
+            }
+        }
+        if !matches!(return_type, Type::Int | Type::Uint | Type::Long | Type::Float | Type::Bool | Type::Void) {
+            self.error(format!("extern functions must return an int, uint, long, float, bool or void, got {:?}", return_type).as_str(), Some(line));
+        }
+    }
+
+    fn extern_stubs(&self) -> String {
+        //an object for every module of the extern functions, with an empty function to fill in for each
+        let mut modules: Vec<(String, String)> = vec![]; //module, stubs
+        for i in self.externs.iter().rev() {
+            if let Stmt::Extern {module, name, params, return_type, ..} = i {
+                let names: Vec<String> = params.iter().map(|p| p.1.literal.clone().unwrap().as_str()).collect();
+                let mut stub = format!("\n            {}: function ({}) {{", name, names.join(", "));
+                for ((t, _), param) in params.iter().zip(&names) {
+                    match t {
+                        Type::Long => stub.push_str(format!("\n                //{} is a BigInt", param).as_str()),
+                        Type::Bool => stub.push_str(format!("\n                //{} is 1 for true and 0 for false", param).as_str()),
+                        Type::String => stub.push_str(format!("\n                {} = kys_string({});", param, param).as_str()),
+                        _ => {},
+                    }
+                }
+                match return_type {
+                    Type::Void => {},
+                    Type::Long => stub.push_str("\n                return 0n; //a BigInt"),
+                    Type::Bool => stub.push_str("\n                return false;"),
+                    t => stub.push_str(format!("\n                return 0; //{}", format!("{:?}", t).to_lowercase()).as_str()),
+                }
+                stub.push_str("\n            },");
+                match modules.iter_mut().find(|m| m.0 == *module) {
+                    Some(m) => m.1.push_str(&stub),
+                    None => modules.push((module.clone(), stub)),
+                }
+            }
+        }
+        let mut stubs = String::new();
+        for (module, functions) in modules {
+            stubs.push_str(format!("\n        {}: {{{}\n        }},", module, functions).as_str());
+        }
+        stubs
+    }
+
+    fn js_binding(name: &str, params: &[(Type, Token)], return_type: &Type) -> String {
+        //longs are BigInts in js, so numbers passed to long parameters are converted, uint results are made unsigned
+        //and the values of a tuple are returned as an array
+        let export = format!("result.instance.exports.{}", name);
+        if !params.iter().any(|p| p.0 == Type::Long) && !matches!(return_type, Type::Uint | Type::Tuple(_)) {
+            return export;
+        }
+        let args: Vec<String> = (0..params.len()).map(|i| format!("p{}", i)).collect();
+        let converted: Vec<String> = params.iter().zip(&args).map(|(p, arg)| {
+            if p.0 == Type::Long {format!("BigInt({})", arg)} else {arg.clone()}
+        }).collect();
+        let mut call = format!("{}({})", export, converted.join(", "));
+        match return_type {
+            Type::Uint => call.push_str(" >>> 0"),
+            Type::Tuple(types) => {
+                let values: Vec<String> = types.iter().enumerate().map(|(i, t)| {
+                    if *t == Type::Uint {format!("r[{}] >>> 0", i)} else {format!("r[{}]", i)}
+                }).collect();
+                call = format!("{{ const r = {}; return [{}]; }}", call, values.join(", "));
+            }
+            _ => {},
+        }
+        format!("({}) => {}", args.join(", "), call)
+    }
+
     fn compile_stmt(&mut self, function: &mut Function, stmt: Stmt) {
         match stmt {
             Stmt::Print{
                 expr,
                 line,
             } => {
-                let t = self.compile_str(function, expr, line); // allow ints + strings, precomputed in rust, add to string hasmap!!
-                self.print_wasm(function, t, line);
+                self.compile_str(function, expr, line); //leaves a pointer to the string on the stack
+                let print = self.helper(Helper::Print);
+                function.instruction(&Instruction::Call(print));
             }
             Stmt::Block{
                 stmts,
                 vars: _,
             } => {
+                self.scopes.push(HashMap::new());
                 for stmt in stmts {
                     self.compile_stmt(function, stmt);
                 }
+                self.end_scope();
             }
             Stmt::Expression(expr) => {
-                self.compile_expr(function, expr);
+                let count = match self.compile_expr(function, expr) {
+                    Type::Void => 0,
+                    Type::Tuple(types) => types.len(),
+                    _ => 1,
+                };
+                for _ in 0..count {
+                    function.instruction(&Instruction::Drop);
+                }
             }
             Stmt::If {
                 condition,
@@ -309,16 +569,17 @@ impl Compiler {
             } => {
                 let t = self.compile_expr(function, condition);
                 //check that the condition is a boolean
-                if let Value::Bool(_) = t {
-                } else {
+                if t != Type::Bool {
                     self.error("an if condition must evaluate to a boolean", Some(line));
                 }
                 function.instruction(&Instruction::If(BlockType::Empty));
+                self.control.push(Control::Other);
                 self.compile_stmt(function, *then_branch);
                 if let Some(else_branch) = else_branch {
                     function.instruction(&Instruction::Else);
                     self.compile_stmt(function, *else_branch);
                 }
+                self.control.pop();
                 function.instruction(&Instruction::End);
             }
             Stmt::Var {
@@ -329,119 +590,446 @@ impl Compiler {
             } => {
                 if let Some(value) = value {
                     let val1 = self.compile_expr(function, value);
-                    let s = val1.as_str();
-                    let index = self.make_string(s);
-                    self.string_vars.insert(name.literal.clone().unwrap().as_str().to_string(), index);
-                    match val1.clone() {
-                        Value::Int(_) => {if t != TokenType::Int {self.error(format!("type mismatch, cannot assign to {:?} \"{}\"", t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));}},
-                        Value::Float(_) => {if t != TokenType::Float {self.error(format!("type mismatch, cannot assign to {:?} \"{}\"", t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));}},
-                        Value::Bool(_) => {if t != TokenType::Bool {self.error(format!("type mismatch, cannot assign to {:?} \"{}\"", t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));}},
-                        Value::String(_) => {if t != TokenType::String {self.error(format!("type mismatch, cannot assign to {:?} \"{}\"", t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));}},
-                        Value::Index(i) => {
-                            if t != TokenType::String {
-                                self.error(format!("type mismatch, cannot assign to {:?} \"{}\"", t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));
-                            } else {
-                                function.instruction(&Instruction::I32Const(i));
-                                self.string_vars.insert(name.literal.clone().unwrap().as_str().to_string(), i);
-                            }
-                        },
+                    if val1 != t {
+                        self.error(format!("type mismatch, cannot assign {:?} to {:?} \"{}\"", val1, t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));
                     }
                 } else {
-                    match t {
-                        TokenType::Int => {function.instruction(&Instruction::I32Const(0));},
-                        TokenType::Float => {function.instruction(&Instruction::F64Const(0.0));},
-                        TokenType::Bool => {function.instruction(&Instruction::I32Const(0));},
-                        TokenType::String => {function.instruction(&Instruction::I32Const(0));},
-                        _ => self.error("default type cant be a string reference", Some(line)),
+                    self.default_value(function, &t);
+                }
+                //declared after the value, so "int x = x + 1;" in a block uses the outer x
+                let index = self.declare_local(&name, t, line);
+                function.instruction(&Instruction::LocalSet(index));
+            }
+            Stmt::Destructure {
+                targets,
+                value,
+                line,
+            } => {
+                let types = match self.compile_expr(function, value) {
+                    Type::Tuple(types) => types,
+                    t => {self.error(format!("cannot assign {:?} to {} variables, the value must be a tuple", t, targets.len()).as_str(), Some(line)); std::process::exit(0);},
+                };
+                if types.len() != targets.len() {
+                    self.error(format!("the tuple has {} values, but {} variables were declared", types.len(), targets.len()).as_str(), Some(line));
+                }
+                for ((t, name), value_type) in targets.iter().zip(&types) {
+                    if t != value_type {
+                        self.error(format!("type mismatch, cannot assign {:?} to {:?} \"{}\"", value_type, t, name.literal.clone().unwrap().as_str()).as_str(), Some(line));
                     }
                 }
-                if self.vars.contains_key(&name.literal.clone().unwrap().as_str()) {
-                    self.error(format!("variable \"{}\" already declared", name.literal.clone().unwrap().as_str()).as_str(), Some(line));
+                //the last value is on top of the stack, so the variables are set in reverse
+                let indices: Vec<u32> = targets.into_iter().map(|(t, name)| self.declare_local(&name, t, line)).collect();
+                for index in indices.into_iter().rev() {
+                    function.instruction(&Instruction::LocalSet(index));
+                }
+            }
+            Stmt::Global {
+                name,
+                value,
+                t,
+                line,
+                ..
+            } => {
+                //constant initial values are part of the global section, everything else is set here
+                let index = self.globals[&name.literal.unwrap().as_str()].0;
+                match value {
+                    Some(value) if Self::const_value(&value).is_some() => {},
+                    Some(value) => {
+                        let val1 = self.compile_expr(function, value);
+                        if val1 != t {
+                            self.error(format!("type mismatch, cannot assign {:?} to global of type {:?}", val1, t).as_str(), Some(line));
+                        }
+                        function.instruction(&Instruction::GlobalSet(index));
+                    }
+                    None => {
+                        self.default_value(function, &t);
+                        function.instruction(&Instruction::GlobalSet(index));
+                    }
                 }
-                self.vars.insert(name.literal.clone().unwrap().as_str().to_string(), (self.vars_count, t));
-                self.vars_count += 1;
-                function.instruction(&Instruction::LocalSet(self.vars.get(&name.literal.unwrap().as_str()).unwrap().0));
             }
             Stmt::While {
                 condition,
                 block,
+                label,
                 line,
             } => {
-                let t = self.compile_expr(function, condition.clone());
-                //check that the condition is a boolean
-                if t != Value::Bool(true) {
-                    self.error("while loop's condition must evaluate to a boolean", Some(line));
+                self.compile_loop(function, condition, *block, None, label, line);
+            }
+            Stmt::For {
+                init,
+                condition,
+                increment,
+                block,
+                label,
+                line,
+            } => {
+                //the variable of the header is only visible in the loop
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.compile_stmt(function, *init);
                 }
-                function.instruction(&Instruction::If(BlockType::Empty));
-                function.instruction(&Instruction::Loop(BlockType::Empty));
-                self.compile_stmt(function, *block);
-                self.compile_expr(function, condition);
-                function.instruction(&Instruction::BrIf(0));
-                function.instruction(&Instruction::End);
-                function.instruction(&Instruction::End);
+                self.compile_loop(function, condition, *block, increment, label, line);
+                self.end_scope();
+            }
+            Stmt::Match {
+                value,
+                arms,
+                line,
+            } => {
+                self.compile_match(function, value, arms, line);
+            }
+            Stmt::Switch {
+                value,
+                cases,
+                default,
+                line,
+            } => {
+                self.compile_switch(function, value, cases, default, line);
             }
             Stmt::Return{
                 returnee,
                 return_type,
                 line,
             } => {
-                match self.compile_expr(function, returnee) {
-                    Value::String(_) => {
-                        if return_type != TokenType::String {
-                            self.error("return type mismatch, cannot return string", Some(line));
-                        }
-                    }
-                    Value::Float(_) => {
-                        if return_type != TokenType::Float {
-                            self.error("return type mismatch, cannot return float", Some(line));
-                        }
-                    }
-                    Value::Int(_) => {
-                        if return_type != TokenType::Int {
-                            self.error("return type mismatch, cannot return int", Some(line));
-                        }
-                    }
-                    Value::Bool(_) => {
-                        if return_type != TokenType::Bool {
-                            self.error("return type mismatch, cannot return bool", Some(line));
-                        }
+                let t = self.compile_expr(function, returnee);
+                if t != return_type {
+                    self.error(format!("return type mismatch, cannot return {:?} from a function returning {:?}", t, return_type).as_str(), Some(line));
+                }
+                function.instruction(&Instruction::Return);
+            }
+            Stmt::Break{
+                label,
+                line,
+            } => {
+                let depth = self.branch_depth(Control::Break(label), line);
+                function.instruction(&Instruction::Br(depth));
+            }
+            Stmt::Continue{
+                label,
+                line,
+            } => {
+                let depth = self.branch_depth(Control::Continue(label), line);
+                function.instruction(&Instruction::Br(depth));
+            }
+            _ => self.error("functions are compiled separately", None),
+        }
+    }
+
+    fn default_value(&mut self, function: &mut Function, t: &Type) {
+        match t {
+            Type::Float => {function.instruction(&Instruction::F64Const(0.0));},
+            Type::Long => {function.instruction(&Instruction::I64Const(0));},
+            Type::List(element) => {
+                let new_list = self.helper(Helper::NewList);
+                function.instruction(&Instruction::I32Const(Self::size_of(element)));
+                function.instruction(&Instruction::Call(new_list));
+            },
+            Type::Struct(name) => {
+                //construct the struct from the default values of its fields, struct fields are left empty (0)
+                let (fields, _) = self.structs[name].clone();
+                for (_, field, _) in fields {
+                    match field {
+                        Type::Struct(_) => {function.instruction(&Instruction::I32Const(0));},
+                        _ => self.default_value(function, &field),
                     }
-                    Value::Index(_) => {
-                        if return_type != TokenType::String {
-                            self.error("return type mismatch, cannot return string", Some(line));
+                }
+                function.instruction(&Instruction::Call(self.funcs[name].0));
+            },
+            //strings and arrays default to offset 0, which holds the empty string (length 0)
+            _ => {function.instruction(&Instruction::I32Const(0));},
+        }
+    }
+
+    fn declare_global(&mut self, name: Token, value: Option<Expr>, t: Type, exported: bool, line: usize) {
+        let name = name.literal.unwrap().as_str();
+        if self.globals.contains_key(&name) {
+            self.error(format!("global \"{}\" already declared", name).as_str(), Some(line));
+        }
+        //exported globals share the export names with the functions, and a global would hide a function value
+        if self.funcs.contains_key(&name) || name == "main" {
+            self.error(format!("global \"{}\" has the same name as a function", name).as_str(), Some(line));
+        }
+        let value = value.and_then(|v| Self::const_value(&v));
+        if let Some(value) = &value {
+            let value_type = match value {
+                Value::Int(_) => Type::Int,
+                Value::Uint(_) => Type::Uint,
+                Value::Long(_) => Type::Long,
+                Value::Float(_) => Type::Float,
+                Value::Bool(_) => Type::Bool,
+                Value::String(s) => {
+                    self.make_string(s.clone());
+                    Type::String
+                }
+            };
+            if value_type != t {
+                self.error(format!("type mismatch, cannot assign {:?} to global of type {:?}", value_type, t).as_str(), Some(line));
+            }
+        }
+        self.globals.insert(name.clone(), (self.kys_globals.len() as u32 + 1, t.clone())); //global 0 is the heap pointer
+        self.kys_globals.push((name, t, value, exported));
+    }
+
+    fn const_value(expr: &Expr) -> Option<Value> {
+        //literals, and negative number literals
+        match expr {
+            Expr::Literal{val} => Some(val.clone()),
+            Expr::Grouping(expr) => Self::const_value(expr),
+            Expr::Unary{operator, expression, ..} if operator.tt == TokenType::Minus => match Self::const_value(expression)? {
+                Value::Int(n) => Some(Value::Int(n.wrapping_neg())),
+                Value::Long(n) => Some(Value::Long(n.wrapping_neg())),
+                Value::Float(n) => Some(Value::Float(-n)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn declare_struct(&mut self, name: String, fields: Vec<(Type, Token)>) {
+        //fields are laid out in declaration order, each aligned to its own size
+        let mut offset = 0;
+        let mut layout = vec![];
+        for (t, field) in fields {
+            let size = Self::size_of(&t);
+            offset = (offset + size - 1) & -size;
+            layout.push((field.literal.unwrap().as_str(), t, offset as u64));
+            offset += size;
+        }
+        self.structs.insert(name.clone(), (layout, (offset + 7) & -8));
+        self.struct_names.push(name);
+    }
+
+    fn constructor(&mut self, name: &str) -> Function {
+        //Point(x, y): allocates the struct and stores every parameter in its field
+        let (fields, size) = self.structs[name].clone();
+        let ptr = fields.len() as u32;
+        let mut f = Function::new(vec![(1, ValType::I32)]);
+        let alloc = self.helper(Helper::Alloc);
+        f.instruction(&Instruction::I32Const(size));
+        f.instruction(&Instruction::Call(alloc));
+        f.instruction(&Instruction::LocalSet(ptr));
+        for (i, (_, t, offset)) in fields.iter().enumerate() {
+            f.instruction(&Instruction::LocalGet(ptr));
+            f.instruction(&Instruction::LocalGet(i as u32));
+            self.store(&mut f, t, *offset);
+        }
+        f.instruction(&Instruction::LocalGet(ptr));
+        f.instruction(&Instruction::End);
+        f
+    }
+
+    fn compile_arms<F: FnOnce(&mut Self, &mut Function)>(&mut self, function: &mut Function, bodies: Vec<Stmt>, exit: Control, dispatch: F) {
+        //block $end { block $arm_n { ... block $arm_0 { dispatch } arm_0; br $end } ... arm_n }
+        //dispatch branches to depth i to run arm i, or to depth n (the number of arms) to run none
+        let count = bodies.len() as u32;
+        function.instruction(&Instruction::Block(BlockType::Empty));
+        self.control.push(exit);
+        for _ in 0..count {
+            function.instruction(&Instruction::Block(BlockType::Empty));
+            self.control.push(Control::Other);
+        }
+        dispatch(self, function);
+        for (i, body) in bodies.into_iter().enumerate() {
+            function.instruction(&Instruction::End);
+            self.control.pop();
+            self.compile_stmt(function, body);
+            if i as u32 != count - 1 {
+                function.instruction(&Instruction::Br(count - 1 - i as u32));
+            }
+        }
+        function.instruction(&Instruction::End);
+        self.control.pop();
+    }
+
+    fn compile_match(&mut self, function: &mut Function, value: Expr, arms: Vec<(Option<Token>, Stmt)>, line: usize) {
+        //br_table jumps to the arm of the value's variant
+        let (patterns, bodies): (Vec<Option<Token>>, Vec<Stmt>) = arms.into_iter().unzip();
+        self.compile_arms(function, bodies, Control::Other, |comp, function| {
+            let name = match comp.compile_expr(function, value) {
+                Type::Enum(name) => name,
+                t => {comp.error(format!("can only match on enums, got {:?}", t).as_str(), Some(line)); std::process::exit(0);},
+            };
+            let variants = comp.enums[&name].clone();
+            let mut targets: Vec<Option<u32>> = vec![None; variants.len()];
+            let mut default = None;
+            for (i, pattern) in patterns.iter().enumerate() {
+                if default.is_some() {
+                    comp.error("unreachable match arm after _", Some(line));
+                }
+                match pattern {
+                    Some(pattern) => {
+                        let variant = pattern.literal.clone().unwrap().as_str();
+                        match variants.iter().position(|v| *v == variant) {
+                            Some(d) if targets[d].is_some() => comp.error(format!("variant \"{}\" is matched twice", variant).as_str(), Some(pattern.line)),
+                            Some(d) => targets[d] = Some(i as u32),
+                            None => comp.error(format!("enum \"{}\" has no variant \"{}\"", name, variant).as_str(), Some(pattern.line)),
                         }
                     }
+                    None => default = Some(i as u32),
                 }
-                function.instruction(&Instruction::Return);
             }
-            Stmt::Break(n) => {
-                function.instruction(&Instruction::Br(n as u32 + 1));
+            //exhaustiveness: every variant needs an arm unless there is a _ arm
+            let missing: Vec<String> = variants.iter().zip(&targets).filter(|(_, t)| t.is_none()).map(|(v, _)| v.clone()).collect();
+            if default.is_none() && !missing.is_empty() {
+                comp.error(format!("match is not exhaustive, missing {}", missing.join(", ")).as_str(), Some(line));
             }
-            _ => self.error("functions are compiled separately", None),
+            let default = default.unwrap_or(patterns.len() as u32);
+            let targets: Vec<u32> = targets.into_iter().map(|t| t.unwrap_or(default)).collect();
+            function.instruction(&Instruction::BrTable(targets.into(), default));
+        });
+    }
+
+    fn compile_switch(&mut self, function: &mut Function, value: Expr, cases: Vec<(Vec<Expr>, Stmt)>, default: Option<Box<Stmt>>, line: usize) {
+        //the case values are constants, every case is an arm and the default comes last
+        let mut values: Vec<(i32, u32)> = vec![]; //value, arm
+        for (i, (exprs, _)) in cases.iter().enumerate() {
+            for expr in exprs {
+                let n = match Self::const_int(expr) {
+                    Some(n) => n,
+                    None => {self.error("a case value must be a constant int", Some(line)); std::process::exit(0);},
+                };
+                if values.iter().any(|v| v.0 == n) {
+                    self.error(format!("case {} is used twice", n).as_str(), Some(line));
+                }
+                values.push((n, i as u32));
+            }
+        }
+        let mut bodies: Vec<Stmt> = cases.into_iter().map(|c| c.1).collect();
+        let default_arm = bodies.len() as u32; //without a default this skips every arm
+        if let Some(default) = default {
+            bodies.push(*default);
         }
+        //break leaves the switch
+        self.compile_arms(function, bodies, Control::Break(None), |comp, function| {
+            if comp.compile_expr(function, value) != Type::Int {
+                comp.error("can only switch on ints", Some(line));
+            }
+            let min = values.iter().map(|v| v.0).min().unwrap_or(0);
+            let max = values.iter().map(|v| v.0).max().unwrap_or(0);
+            let range = max as i64 - min as i64 + 1;
+            if range <= 4 * values.len() as i64 + 4 {
+                //dense cases use a table indexed by value - min, values outside of it go to the default
+                let mut targets = vec![default_arm; range as usize];
+                for (n, arm) in &values {
+                    targets[(*n as i64 - min as i64) as usize] = *arm;
+                }
+                function.instruction(&Instruction::I32Const(min));
+                function.instruction(&Instruction::I32Sub);
+                function.instruction(&Instruction::BrTable(targets.into(), default_arm));
+            } else {
+                //sparse cases compare the value with every case
+                function.instruction(&Instruction::LocalSet(comp.scratch));
+                for (n, arm) in &values {
+                    function.instruction(&Instruction::LocalGet(comp.scratch));
+                    function.instruction(&Instruction::I32Const(*n));
+                    function.instruction(&Instruction::I32Eq);
+                    function.instruction(&Instruction::BrIf(*arm));
+                }
+                function.instruction(&Instruction::Br(default_arm));
+            }
+        });
     }
 
-    fn compile_expr(&mut self, function: &mut Function, expr: Expr) -> Value {
+    fn const_int(expr: &Expr) -> Option<i32> {
+        match expr {
+            Expr::Literal{val: Value::Int(n)} => Some(*n),
+            Expr::Grouping(expr) => Self::const_int(expr),
+            Expr::Unary{operator, expression, ..} if operator.tt == TokenType::Minus => Self::const_int(expression).map(|n| n.wrapping_neg()),
+            _ => None,
+        }
+    }
+
+    fn field(&mut self, function: &mut Function, object: Expr, name: &Token, line: usize) -> (Type, u64) {
+        //leaves the pointer to the struct on the stack and returns the field's type and offset
+        let name = name.literal.clone().unwrap().as_str();
+        let struct_name = match self.compile_expr(function, object) {
+            Type::Struct(struct_name) => struct_name,
+            t => {self.error(format!("cannot access field \"{}\" of {:?}", name, t).as_str(), Some(line)); std::process::exit(0);},
+        };
+        //a struct that was never assigned is 0, which is the empty string and must not be written
+        function.instruction(&Instruction::LocalTee(self.scratch));
+        function.instruction(&Instruction::I32Eqz);
+        function.instruction(&Instruction::If(BlockType::Empty));
+        function.instruction(&Instruction::Unreachable);
+        function.instruction(&Instruction::End);
+        function.instruction(&Instruction::LocalGet(self.scratch));
+        match self.structs[&struct_name].0.iter().find(|f| f.0 == name) {
+            Some((_, t, offset)) => (t.clone(), *offset),
+            None => {self.error(format!("struct \"{}\" has no field \"{}\"", struct_name, name).as_str(), Some(line)); std::process::exit(0);},
+        }
+    }
+
+    fn compile_loop(&mut self, function: &mut Function, condition: Expr, block: Stmt, increment: Option<Expr>, label: Option<String>, line: usize) {
+        //if condition { loop { block { body } increment; br_if condition } }
+        //break jumps out of the if, continue jumps to the end of the block
+        let t = self.compile_expr(function, condition.clone());
+        //check that the condition is a boolean
+        if t != Type::Bool {
+            self.error("a loop's condition must evaluate to a boolean", Some(line));
+        }
+        function.instruction(&Instruction::If(BlockType::Empty));
+        function.instruction(&Instruction::Loop(BlockType::Empty));
+        function.instruction(&Instruction::Block(BlockType::Empty));
+        self.control.push(Control::Break(label.clone()));
+        self.control.push(Control::Other);
+        self.control.push(Control::Continue(label));
+        self.compile_stmt(function, block);
+        self.control.truncate(self.control.len() - 3);
+        function.instruction(&Instruction::End);
+        if let Some(increment) = increment {
+            self.compile_stmt(function, Stmt::Expression(increment));
+        }
+        self.compile_expr(function, condition);
+        function.instruction(&Instruction::BrIf(0));
+        function.instruction(&Instruction::End);
+        function.instruction(&Instruction::End);
+    }
+
+    //the relative depth of the innermost enclosing target, an unlabeled break/continue targets the innermost loop
+    fn branch_depth(&self, target: Control, line: usize) -> u32 {
+        let found = self.control.iter().rposition(|c| match (c, &target) {
+            (Control::Break(_), Control::Break(None)) | (Control::Continue(_), Control::Continue(None)) => true,
+            _ => *c == target,
+        });
+        match found {
+            Some(i) => (self.control.len() - 1 - i) as u32,
+            None => {
+                self.error("break or continue outside of a loop", Some(line));
+                std::process::exit(0);
+            }
+        }
+    }
+
+    fn compile_expr(&mut self, function: &mut Function, expr: Expr) -> Type {
         match expr {
             Expr::Grouping(expr) => self.compile_expr(function, *expr),
-            Expr::Literal{val, line} => {
-                return match val {
+            Expr::Literal{val} => {
+                match val {
                     Value::Int(n) => {
                         function.instruction(&Instruction::I32Const(n));
-                        Value::Int(n)
+                        Type::Int
+                    },
+                    Value::Uint(n) => {
+                        function.instruction(&Instruction::I32Const(n as i32));
+                        Type::Uint
+                    },
+                    Value::Long(n) => {
+                        function.instruction(&Instruction::I64Const(n));
+                        Type::Long
                     },
                     Value::Float(n) => {
                         function.instruction(&Instruction::F64Const(n));
-                        Value::Float(n)
+                        Type::Float
                     },
                     Value::Bool(b) => {
                         function.instruction(&Instruction::I32Const(if b { 1 } else { 0 }));
-                        Value::Bool(b)
+                        Type::Bool
                     },
                     Value::String(s) => {
-                        Value::Index(self.make_string(s))
+                        let offset = self.make_string(s);
+                        function.instruction(&Instruction::I32Const(offset));
+                        Type::String
                     },
-                    _ => {self.error("undefined value (a string reference isn't a value)", Some(line)); Value::Int(0)}
                 }
             },
             Expr::Assign {
@@ -450,38 +1038,11 @@ impl Compiler {
                 line,
             } => {
                 let val = self.compile_expr(function, *value);
-                match self.vars.get(&name.literal.clone().unwrap().as_str()).unwrap().1 {
-                    TokenType::Int => {
-                        if let Value::Int(_) = val {
-                        } else {
-                            self.error(format!("Cannot assign non-integer value to variable \"{}\" of type Int", name.literal.clone().unwrap().as_str()).as_str(), Some(line));
-                        }
-                    },
-                    TokenType::Float => {
-                        if let Value::Float(_) = val {
-                        } else {
-                            self.error(format!("Cannot assign non-float value to variable \"{}\" of type Float", name.literal.clone().unwrap().as_str()).as_str(), Some(line));
-                        }
-                    },
-                    TokenType::Bool => {
-                        if let Value::Bool(_) = val {
-                        } else {
-                            self.error(format!("Cannot assign non-boolean value to variable \"{}\" of type Bool", name.literal.clone().unwrap().as_str()).as_str(), Some(line));
-                        }
-                    },
-                    TokenType::String => {
-                        if let Value::String(_) = val {
-                        } else {
-                            self.error(format!("Cannot assign non-string value to variable \"{}\" of type String", name.literal.clone().unwrap().as_str()).as_str(), Some(line));
-                        }
-                    },
-                    _ => self.error("cannot assign a string reference to a variable", Some(line)),
+                let t = self.store_var(function, &name, line);
+                if val != t {
+                    self.error(format!("Cannot assign {:?} value to variable \"{}\" of type {:?}", val, name.literal.clone().unwrap().as_str(), t).as_str(), Some(line));
                 }
-                function.instruction(&Instruction::LocalSet(self.vars.get(&name.literal.clone().unwrap().as_str()).unwrap().0));
-                let s = val.as_str();
-                let index = self.make_string(s);
-                self.string_vars.insert(name.literal.clone().unwrap().as_str().to_string(), index);
-                Value::Int(0)
+                Type::Void
             }
             Expr::Binary {
                 left,
@@ -489,37 +1050,48 @@ impl Compiler {
                 right,
                 line,
             } => {
+                if operator.tt == TokenType::And || operator.tt == TokenType::Or {
+                    return self.logical(function, *left, *right, operator.tt, line);
+                }
                 let t1 = self.compile_expr(function, *left);
                 let t2 = self.compile_expr(function, *right);
                 self.bin(function, &t1, &t2, operator.tt, line)
             }
+            Expr::Lambda{params, body, line} => self.lambda(function, params, *body, line),
+            Expr::Tuple {values, line} => {
+                //every value stays on the stack, the tuple is returned or destructured right away
+                let mut types = vec![];
+                for value in values {
+                    let t = self.compile_expr(function, value);
+                    if matches!(t, Type::Void | Type::Tuple(_)) {
+                        self.error(format!("a tuple cannot contain {:?}", t).as_str(), Some(line));
+                    }
+                    types.push(t);
+                }
+                Type::Tuple(types)
+            }
             Expr::Variable{name, line} => {
-                function.instruction(&Instruction::LocalGet(self.vars.get(&name.literal.clone().unwrap_or_else(|| {
-                    self.error(&format!("cannot get variable {}, perhaps it contains a function call?", name.literal.clone().unwrap().as_str()), Some(line));
-                    std::process::exit(0);
-                }).as_str()).unwrap_or_else(|| {
-                    self.error(&format!("cannot get variable {}, perhaps it contains a function call?", name.literal.clone().unwrap().as_str()), Some(line));
-                    std::process::exit(0);
-                }).0));
-                match self.vars.get(&name.literal.clone().unwrap_or_else(|| {
-                    self.error(&format!("cannot get variable {}, perhaps it contains a function call?", name.literal.clone().unwrap().as_str()), Some(line));
-                    std::process::exit(0);
-                }).as_str()).unwrap_or_else(|| {
-                    self.error(&format!("cannot get variable {}, perhaps it contains a function call?", name.literal.clone().unwrap().as_str()), Some(line));
-                    std::process::exit(0);
-                }).1 {
-                    TokenType::Int => Value::Int(0),
-                    TokenType::Float => Value::Float(0.0),
-                    TokenType::Bool => Value::Bool(true),
-                    TokenType::String => Value::String("".to_owned()),
-                    _ => {self.error("a variable cannot be a string reference", Some(line)); Value::Int(0)}
+                //the name of a function is a function value, unless a variable has that name
+                let n = name.literal.clone().unwrap().as_str();
+                if self.find_var(&n).is_none() && self.funcs.contains_key(&n) {
+                    return self.function_value(function, &n);
                 }
+                self.load_var(function, &name, line)
             }
             Expr::Unary {
                 operator,
                 expression,
                 line,
             } => {
+                //negative int literals are emitted as constants
+                if let (TokenType::Minus, Expr::Literal{val: Value::Int(n)}) = (operator.tt, &*expression) {
+                    function.instruction(&Instruction::I32Const(n.wrapping_neg()));
+                    return Type::Int;
+                }
+                if let (TokenType::Minus, Expr::Literal{val: Value::Long(n)}) = (operator.tt, &*expression) {
+                    function.instruction(&Instruction::I64Const(n.wrapping_neg()));
+                    return Type::Long;
+                }
                 let t1 = self.compile_expr(function, *expression);
                 self.unary(function, &t1, operator.tt, line);
                 t1
@@ -529,154 +1101,710 @@ impl Compiler {
                 arguments,
                 line,
             } => {
-                for arg in arguments {
-                    self.compile_expr(function, arg);
-                }
-                match *callee {
-                    Expr::Variable{name, ..} => {
-                        function.instruction(&Instruction::Call(self.funcs.get(&name.literal.clone().unwrap().as_str()).unwrap().0));
-                        match self.funcs.get(&name.literal.clone().unwrap().as_str()).unwrap().1 {
-                            TokenType::Int => Value::Int(0),
-                            TokenType::Float => Value::Float(0.0),
-                            TokenType::Bool => Value::Bool(true),
-                            TokenType::String => Value::String("".to_owned()),
-                            _ => {self.error("a variable cannot be a string reference", Some(line)); Value::Int(0)}
-                        }
+                let token = match *callee {
+                    Expr::Variable{name, ..} => name,
+                    _ => {self.error("the callee must be a variable", Some(line)); std::process::exit(0);},
+                };
+                let name = token.literal.clone().unwrap().as_str();
+                if let Some((_, _, Type::Func(params, return_type))) = self.find_var(&name) {
+                    return self.call_indirect(function, &token, &params, *return_type, arguments, line);
+                }
+                let (index, params, return_type) = match self.funcs.get(&name) {
+                    Some(func) => func.clone(),
+                    None => return self.builtin(function, &name, arguments, line),
+                };
+                if params.len() != arguments.len() {
+                    self.error(format!("function \"{}\" takes {} arguments, but {} were given", name, params.len(), arguments.len()).as_str(), Some(line));
+                }
+                for (arg, param) in arguments.into_iter().zip(params) {
+                    let t = self.compile_expr(function, arg);
+                    if t != param {
+                        self.error(format!("cannot pass {:?} to a parameter of type {:?} in function \"{}\"", t, param, name).as_str(), Some(line));
                     }
-                    _ => {self.error("a variable cannot be a string reference", Some(line)); Value::Int(0)},
                 }
+                function.instruction(&Instruction::Call(index));
+                return_type
             }
-        }
-    }
-
-    fn compile_str(&mut self, function: &mut Function, expr: Expr, line: usize) -> i32 {
-        match expr {
-            Expr::Grouping(expr) => {self.compile_str(function, *expr, line)},
-            Expr::Binary {
-                left,
-                right,
+            Expr::Index {
+                array,
+                index,
                 line,
-                ..
             } => {
-                let t1 = self.compile_str(function, *left, line);
-                let t2 = self.compile_str(function, *right, line);
-                self.add_strings(t1, t2, line)
+                let element = self.element_addr(function, *array, *index, line);
+                self.load(function, &element, 0);
+                element
             }
-            Expr::Literal{
-                val,
-                ..
+            Expr::SetIndex {
+                array,
+                index,
+                value,
+                operator,
+                line,
             } => {
-                self.make_string(val.as_str())
+                let element = self.element_addr(function, *array, *index, line);
+                let t = self.compound(function, &element, 0, operator, *value, line);
+                if t != element {
+                    self.error(format!("cannot store {:?} in an array of {:?}", t, element).as_str(), Some(line));
+                }
+                self.store(function, &element, 0);
+                Type::Void
             }
-            Expr::Variable{
-                name,
+            Expr::NewArray {
+                t,
+                size,
                 line,
             } => {
-                return self.string_vars.get(&name.literal.clone().unwrap().as_str()).unwrap_or_else(|| {
-                    self.error(&format!("cannot stringify variable {}", name.literal.clone().unwrap().as_str()), Some(line));
-                    std::process::exit(0);
-                }).clone();
+                if self.compile_expr(function, *size) != Type::Int {
+                    self.error("array size must be an int", Some(line));
+                }
+                function.instruction(&Instruction::I32Const(Self::size_of(&t)));
+                let new_array = self.helper(Helper::NewArray);
+                function.instruction(&Instruction::Call(new_array));
+                Type::Array(Box::new(t))
             }
-            Expr::Call {
-                callee,
-                arguments,
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
                 line,
             } => {
-                for arg in arguments {
-                    self.compile_expr(function, arg);
+                if self.compile_expr(function, *condition) != Type::Bool {
+                    self.error("the condition of a conditional expression must be a boolean", Some(line));
                 }
-                match *callee {
-                    Expr::Variable {
-                        ..
-                    } => {
-                        // let func_num = self.funcs.get(&t.literal.clone().unwrap().as_str()).unwrap().0;
-                        // function.instruction(&Instruction::Call(self.funcs.get(&t.literal.clone().unwrap().as_str()).unwrap().0));
-                        // self.precompute_string(self.kys_funcs[func_num as usize - 2].clone())
-                        self.error("we are sorry, keyscript does not support printing function calls yet. (blame wasm's shitty strings). please print the return value inside the function before returning it.", Some(line)); 0
+                //the if needs the result type up front, so the first arm is compiled before it is placed
+                let (then_code, t) = self.compile_detached(*then_branch);
+                if t == Type::Void {
+                    self.error("the arms of a conditional expression must have a value", Some(line));
+                }
+                let block_type = match &t {
+                    Type::Tuple(types) => {
+                        let results = types.iter().map(|t| self.val_type(t, Some(line))).collect();
+                        BlockType::FunctionType(self.signature(vec![], results))
+                    }
+                    _ => BlockType::Result(self.val_type(&t, Some(line))),
+                };
+                function.instruction(&Instruction::If(block_type));
+                function.raw(then_code);
+                function.instruction(&Instruction::Else);
+                let t2 = self.compile_expr(function, *else_branch);
+                if t2 != t {
+                    self.error(format!("the arms of a conditional expression have different types, {:?} and {:?}", t, t2).as_str(), Some(line));
+                }
+                function.instruction(&Instruction::End);
+                t
+            }
+            Expr::Cast {
+                t,
+                value,
+                line,
+            } => {
+                let from = self.compile_expr(function, *value);
+                self.cast(function, &from, &t, line);
+                t
+            }
+            Expr::Get {
+                object,
+                name,
+                line,
+            } => {
+                //Color.Red is the discriminant of a variant
+                if let Expr::Variable{name: type_name, ..} = &*object {
+                    let type_name = type_name.literal.clone().unwrap().as_str();
+                    if let Some(variants) = self.enums.get(&type_name) {
+                        let variant = name.literal.clone().unwrap().as_str();
+                        match variants.iter().position(|v| *v == variant) {
+                            Some(d) => {function.instruction(&Instruction::I32Const(d as i32));},
+                            None => self.error(format!("enum \"{}\" has no variant \"{}\"", type_name, variant).as_str(), Some(line)),
+                        }
+                        return Type::Enum(type_name);
                     }
-                    _ => {self.error("the callee must be a variable", Some(line)); 0},
                 }
+                let (t, offset) = self.field(function, *object, &name, line);
+                self.load(function, &t, offset);
+                t
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                operator,
+                line,
+            } => {
+                let (field, offset) = self.field(function, *object, &name, line);
+                let t = self.compound(function, &field, offset, operator, *value, line);
+                if t != field {
+                    self.error(format!("cannot store {:?} in field \"{}\" of type {:?}", t, name.literal.clone().unwrap().as_str(), field).as_str(), Some(line));
+                }
+                self.store(function, &field, offset);
+                Type::Void
+            }
+        }
+    }
+
+    fn compound(&mut self, function: &mut Function, t: &Type, offset: u64, operator: Option<Token>, value: Expr, line: usize) -> Type {
+        //with the address on the stack, a[i] += v loads the old value through a copy of it so a and i run once
+        let operator = match operator {
+            Some(operator) => operator,
+            None => return self.compile_expr(function, value),
+        };
+        function.instruction(&Instruction::LocalTee(self.scratch));
+        function.instruction(&Instruction::LocalGet(self.scratch));
+        self.load(function, t, offset);
+        let t2 = self.compile_expr(function, value);
+        self.bin(function, t, &t2, operator.tt, line)
+    }
+
+    fn compile_detached(&mut self, expr: Expr) -> (Vec<u8>, Type) {
+        //compiles an expression into its own buffer, returning the raw instructions and the type
+        let mut f = Function::new(vec![]);
+        let t = self.compile_expr(&mut f, expr);
+        let mut body = vec![];
+        f.encode(&mut body); //[body size (leb128)][0 local declarations][instructions]
+        let start = body.iter().position(|b| b & 0x80 == 0).unwrap() + 2;
+        (body[start..].to_vec(), t)
+    }
+
+    fn builtin(&mut self, function: &mut Function, name: &str, arguments: Vec<Expr>, line: usize) -> Type {
+        //built in functions, only used when there is no keyscript function with the same name
+        match name {
+            "len" => {
+                if arguments.len() != 1 {
+                    self.error("len takes exactly 1 argument", Some(line));
+                }
+                match self.compile_expr(function, arguments[0].clone()) {
+                    //strings, arrays and lists all start with their length, the length of a string is in bytes
+                    Type::String | Type::Array(_) | Type::List(_) => {function.instruction(&Instruction::I32Load(mem(2)));},
+                    t => self.error(format!("cannot get the length of {:?}", t).as_str(), Some(line)),
+                }
+                Type::Int
+            }
+            "push" => {
+                if arguments.len() != 2 {
+                    self.error("push takes exactly 2 arguments, a list and a value", Some(line));
+                }
+                let element = self.list_arg(function, arguments[0].clone(), "push", line);
+                let list_push = self.helper(Helper::ListPush);
+                function.instruction(&Instruction::I32Const(Self::size_of(&element)));
+                function.instruction(&Instruction::Call(list_push));
+                let t = self.compile_expr(function, arguments[1].clone());
+                if t != element {
+                    self.error(format!("cannot push {:?} to a list of {:?}", t, element).as_str(), Some(line));
+                }
+                self.store(function, &element, 0);
+                Type::Void
+            }
+            "pop" => {
+                if arguments.len() != 1 {
+                    self.error("pop takes exactly 1 argument", Some(line));
+                }
+                let element = self.list_arg(function, arguments[0].clone(), "pop", line);
+                let list_pop = self.helper(Helper::ListPop);
+                function.instruction(&Instruction::I32Const(Self::size_of(&element)));
+                function.instruction(&Instruction::Call(list_pop));
+                self.load(function, &element, 0);
+                element
+            }
+            "substr" => self.string_builtin(function, name, arguments, &[Type::String, Type::Int, Type::Int], Helper::Substr, line),
+            "indexOf" => {
+                if arguments.len() != 2 && arguments.len() != 3 {
+                    self.error("indexOf takes 2 or 3 arguments, a string, the string to find and the index to start from", Some(line));
+                }
+                //without a start index the search starts at 0
+                let mut arguments = arguments;
+                if arguments.len() == 2 {
+                    arguments.push(Expr::Literal {val: Value::Int(0)});
+                }
+                self.string_builtin(function, name, arguments, &[Type::String, Type::String, Type::Int], Helper::Find, line);
+                Type::Int
+            }
+            "toUpper" => self.string_builtin(function, name, arguments, &[Type::String], Helper::ToUpper, line),
+            "toLower" => self.string_builtin(function, name, arguments, &[Type::String], Helper::ToLower, line),
+            "trim" => self.string_builtin(function, name, arguments, &[Type::String], Helper::Trim, line),
+            "split" => {
+                self.string_builtin(function, name, arguments, &[Type::String, Type::String], Helper::Split, line);
+                Type::List(Box::new(Type::String))
             }
-            _ => {self.error("this expression cant be used in a print statement", Some(line)); 0}
+            _ => {self.error(format!("undefined function \"{}\"", name).as_str(), Some(line)); std::process::exit(0);},
         }
     }
 
-    fn bin(&mut self, function: &mut Function, t1: &Value, t2: &Value, operator: TokenType, line: usize) -> Value {
+    fn string_builtin(&mut self, function: &mut Function, name: &str, arguments: Vec<Expr>, params: &[Type], helper: Helper, line: usize) -> Type {
+        //the string functions call a runtime helper with their arguments, most of them return a string
+        if arguments.len() != params.len() {
+            self.error(format!("{} takes exactly {} arguments, but {} were given", name, params.len(), arguments.len()).as_str(), Some(line));
+        }
+        for (arg, param) in arguments.into_iter().zip(params) {
+            let t = self.compile_expr(function, arg);
+            if t != *param {
+                self.error(format!("cannot pass {:?} to a parameter of type {:?} in function \"{}\"", t, param, name).as_str(), Some(line));
+            }
+        }
+        let index = self.helper(helper);
+        function.instruction(&Instruction::Call(index));
+        Type::String
+    }
+
+    fn list_arg(&mut self, function: &mut Function, list: Expr, name: &str, line: usize) -> Type {
+        match self.compile_expr(function, list) {
+            Type::List(element) => *element,
+            t => {self.error(format!("{} expects a list, got {:?}", name, t).as_str(), Some(line)); std::process::exit(0);},
+        }
+    }
+
+    fn element_addr(&mut self, function: &mut Function, array: Expr, index: Expr, line: usize) -> Type {
+        //leaves the address of array[index] on the stack (bounds checked) and returns the element type
+        let (element, helper) = match self.compile_expr(function, array) {
+            Type::Array(element) => (*element, Helper::ArrayIndex),
+            Type::List(element) => (*element, Helper::ListIndex),
+            t => {self.error(format!("cannot index into {:?}", t).as_str(), Some(line)); std::process::exit(0);},
+        };
+        if self.compile_expr(function, index) != Type::Int {
+            self.error("array index must be an int", Some(line));
+        }
+        function.instruction(&Instruction::I32Const(Self::size_of(&element)));
+        let index = self.helper(helper);
+        function.instruction(&Instruction::Call(index));
+        element
+    }
+
+    fn load(&self, function: &mut Function, t: &Type, offset: u64) {
+        match t {
+            Type::Float => function.instruction(&Instruction::F64Load(MemArg {offset, ..mem(3)})),
+            Type::Long => function.instruction(&Instruction::I64Load(MemArg {offset, ..mem(3)})),
+            _ => function.instruction(&Instruction::I32Load(MemArg {offset, ..mem(2)})),
+        };
+    }
+
+    fn store(&self, function: &mut Function, t: &Type, offset: u64) {
+        match t {
+            Type::Float => function.instruction(&Instruction::F64Store(MemArg {offset, ..mem(3)})),
+            Type::Long => function.instruction(&Instruction::I64Store(MemArg {offset, ..mem(3)})),
+            _ => function.instruction(&Instruction::I32Store(MemArg {offset, ..mem(2)})),
+        };
+    }
+
+    fn size_of(t: &Type) -> i32 {
+        match t {
+            Type::Float | Type::Long => 8,
+            _ => 4,
+        }
+    }
+
+    fn val_type(&self, t: &Type, line: Option<usize>) -> ValType {
+        match t {
+            Type::Float => ValType::F64,
+            Type::Long => ValType::I64,
+            Type::Void => {self.error("void is not a value type", line); std::process::exit(0);},
+            Type::Tuple(_) => {self.error("a tuple is not a single value, use int a, int b = f(); to get its values", line); std::process::exit(0);},
+            _ => ValType::I32, //ints, bools, and pointers to strings, arrays and lists
+        }
+    }
+
+    fn begin_function(&mut self, params: &[(Type, Token)], body: &[Stmt]) {
+        //every function numbers its locals from 0, starting with the parameters
+        self.scopes = vec![HashMap::new()];
+        self.locals_count = 0;
+        self.ended.clear();
+        self.declared.clear();
+        for (t, name) in params {
+            self.declare_local(name, t.clone(), name.line);
+        }
+        for stmt in body {
+            Self::declared_names(stmt, &mut self.declared);
+        }
+    }
+
+    fn declared_names(stmt: &Stmt, names: &mut HashSet<String>) {
+        match stmt {
+            Stmt::Var {name, ..} => {names.insert(name.literal.clone().unwrap().as_str());},
+            Stmt::Destructure {targets, ..} => targets.iter().for_each(|(_, name)| {names.insert(name.literal.clone().unwrap().as_str());}),
+            Stmt::Block {stmts, ..} => stmts.iter().for_each(|s| Self::declared_names(s, names)),
+            Stmt::If {then_branch, else_branch, ..} => {
+                Self::declared_names(then_branch, names);
+                if let Some(else_branch) = else_branch {
+                    Self::declared_names(else_branch, names);
+                }
+            }
+            Stmt::While {block, ..} => Self::declared_names(block, names),
+            Stmt::For {init, block, ..} => {
+                if let Some(init) = init {
+                    Self::declared_names(init, names);
+                }
+                Self::declared_names(block, names);
+            }
+            Stmt::Match {arms, ..} => arms.iter().for_each(|(_, s)| Self::declared_names(s, names)),
+            Stmt::Switch {cases, default, ..} => {
+                cases.iter().for_each(|(_, s)| Self::declared_names(s, names));
+                if let Some(default) = default {
+                    Self::declared_names(default, names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn declare_local(&mut self, name: &Token, t: Type, line: usize) -> u32 {
+        //a variable can shadow one of an enclosing block, but not one of the same block
+        let name = name.literal.clone().unwrap().as_str();
+        if self.scopes.last().unwrap().contains_key(&name) {
+            self.error(format!("variable \"{}\" already declared in this scope", name).as_str(), Some(line));
+        }
+        let index = self.locals_count;
+        self.scopes.last_mut().unwrap().insert(name, (index, t));
+        self.locals_count += 1;
+        index
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        self.ended.extend(scope.into_keys());
+    }
+
+    fn find_var(&self, name: &str) -> Option<(bool, u32, Type)> {
+        //innermost local first, then globals; returns whether it is a local, the index and the type
+        for scope in self.scopes.iter().rev() {
+            if let Some((index, t)) = scope.get(name) {
+                return Some((true, *index, t.clone()));
+            }
+        }
+        self.globals.get(name).map(|(index, t)| (false, *index, t.clone()))
+    }
+
+    fn lookup(&self, name: &Token, line: usize) -> (bool, u32, Type) {
+        let name = name.literal.clone().unwrap().as_str();
+        if let Some(var) = self.find_var(&name) {
+            return var;
+        }
+        if self.ended.contains(&name) {
+            self.error(format!("variable \"{}\" is out of scope, it was declared in a block that has ended", name).as_str(), Some(line));
+        } else if self.declared.contains(&name) {
+            self.error(format!("variable \"{}\" is used before its declaration", name).as_str(), Some(line));
+        } else {
+            self.error(format!("undeclared variable \"{}\"", name).as_str(), Some(line));
+        }
+        std::process::exit(0);
+    }
+
+    fn load_var(&self, function: &mut Function, name: &Token, line: usize) -> Type {
+        let (local, index, t) = self.lookup(name, line);
+        if local {
+            function.instruction(&Instruction::LocalGet(index));
+        } else {
+            function.instruction(&Instruction::GlobalGet(index));
+        }
+        t
+    }
+
+    fn store_var(&self, function: &mut Function, name: &Token, line: usize) -> Type {
+        //sets the variable to the value on top of the stack and returns its type
+        let (local, index, t) = self.lookup(name, line);
+        if local {
+            function.instruction(&Instruction::LocalSet(index));
+        } else {
+            function.instruction(&Instruction::GlobalSet(index));
+        }
+        t
+    }
+
+    fn compile_str(&mut self, function: &mut Function, expr: Expr, line: usize) {
+        //every "+" of a print statement concatenates, anything else is evaluated and converted to a string
+        match expr {
+            Expr::Binary {
+                left,
+                right,
+                ..
+            } => {
+                self.compile_str(function, *left, line);
+                self.compile_str(function, *right, line);
+                let concat = self.helper(Helper::Concat);
+                function.instruction(&Instruction::Call(concat));
+            }
+            _ => {
+                let t = self.compile_expr(function, expr);
+                self.stringify(function, &t, line);
+            }
+        }
+    }
+
+    fn stringify(&mut self, function: &mut Function, t: &Type, line: usize) {
+        //converts the value on top of the stack to a string
+        match t {
+            Type::String => {},
+            Type::Int => {
+                let int_to_str = self.helper(Helper::IntToStr);
+                function.instruction(&Instruction::I64ExtendI32S);
+                function.instruction(&Instruction::Call(int_to_str));
+            }
+            Type::Uint => {
+                let int_to_str = self.helper(Helper::IntToStr);
+                function.instruction(&Instruction::I64ExtendI32U);
+                function.instruction(&Instruction::Call(int_to_str));
+            }
+            Type::Long => {
+                let int_to_str = self.helper(Helper::IntToStr);
+                function.instruction(&Instruction::Call(int_to_str));
+            }
+            Type::Float => {
+                let float_to_str = self.helper(Helper::FloatToStr);
+                function.instruction(&Instruction::Call(float_to_str));
+            }
+            Type::Bool => {
+                let t = self.make_string("true".to_string());
+                let f = self.make_string("false".to_string());
+                function.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                function.instruction(&Instruction::I32Const(t));
+                function.instruction(&Instruction::Else);
+                function.instruction(&Instruction::I32Const(f));
+                function.instruction(&Instruction::End);
+            }
+            Type::Void => self.error("cannot print the result of a void function", Some(line)),
+            _ => self.error(format!("cannot print {:?}", t).as_str(), Some(line)),
+        }
+    }
+
+    fn logical(&mut self, function: &mut Function, left: Expr, right: Expr, operator: TokenType, line: usize) -> Type {
+        //a && b is "if a { b } else { false }", a || b is "if a { true } else { b }", so b only runs when needed
+        if self.compile_expr(function, left) != Type::Bool {
+            self.error("the left side of a logical operator must be a boolean", Some(line));
+        }
+        function.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        if operator == TokenType::Or {
+            function.instruction(&Instruction::I32Const(1));
+            function.instruction(&Instruction::Else);
+        }
+        if self.compile_expr(function, right) != Type::Bool {
+            self.error("the right side of a logical operator must be a boolean", Some(line));
+        }
+        if operator == TokenType::And {
+            function.instruction(&Instruction::Else);
+            function.instruction(&Instruction::I32Const(0));
+        }
+        function.instruction(&Instruction::End);
+        Type::Bool
+    }
+
+    fn bin(&mut self, function: &mut Function, t1: &Type, t2: &Type, operator: TokenType, line: usize) -> Type {
         match (t1, t2) {
-            (Value::Int(_), Value::Int(_)) => {
+            (Type::Int, Type::Int) => {
                 match operator {
-                    TokenType::Plus => {function.instruction(&Instruction::I32Add); Value::Int(0)},
-                    TokenType::Minus => {function.instruction(&Instruction::I32Sub); Value::Int(0)},
-                    TokenType::Star => {function.instruction(&Instruction::I32Mul); Value::Int(0)},
-                    TokenType::Slash => {function.instruction(&Instruction::I32DivU); Value::Int(0)},
-                    TokenType::EqualEqual => {function.instruction(&Instruction::I32Eq); Value::Bool(true)},
-                    TokenType::BangEqual => {function.instruction(&Instruction::I32Ne); Value::Bool(true)},
-                    TokenType::Less => {function.instruction(&Instruction::I32LtU); Value::Bool(true)},
-                    TokenType::LessEqual => {function.instruction(&Instruction::I32LeU); Value::Bool(true)},
-                    TokenType::Greater => {function.instruction(&Instruction::I32GtU); Value::Bool(true)},
-                    TokenType::GreaterEqual => {function.instruction(&Instruction::I32GeU); Value::Bool(true)},
-                    TokenType::Modulo => {function.instruction(&Instruction::I32RemU); Value::Int(0)},
-                    _ => {self.error("undefined operation between 2 integers", Some(line)); Value::Bool(true)},
-                }
-            }
-            (Value::Float(_), Value::Float(_)) => {
+                    TokenType::Plus => {function.instruction(&Instruction::I32Add); Type::Int},
+                    TokenType::Minus => {function.instruction(&Instruction::I32Sub); Type::Int},
+                    TokenType::Star => {function.instruction(&Instruction::I32Mul); Type::Int},
+                    TokenType::Slash => {function.instruction(&Instruction::I32DivS); Type::Int},
+                    TokenType::EqualEqual => {function.instruction(&Instruction::I32Eq); Type::Bool},
+                    TokenType::BangEqual => {function.instruction(&Instruction::I32Ne); Type::Bool},
+                    TokenType::Less => {function.instruction(&Instruction::I32LtS); Type::Bool},
+                    TokenType::LessEqual => {function.instruction(&Instruction::I32LeS); Type::Bool},
+                    TokenType::Greater => {function.instruction(&Instruction::I32GtS); Type::Bool},
+                    TokenType::GreaterEqual => {function.instruction(&Instruction::I32GeS); Type::Bool},
+                    TokenType::Modulo => {function.instruction(&Instruction::I32RemS); Type::Int},
+                    TokenType::BitAnd => {function.instruction(&Instruction::I32And); Type::Int},
+                    TokenType::BitOr => {function.instruction(&Instruction::I32Or); Type::Int},
+                    TokenType::BitXor => {function.instruction(&Instruction::I32Xor); Type::Int},
+                    TokenType::ShiftLeft => {function.instruction(&Instruction::I32Shl); Type::Int},
+                    TokenType::ShiftRight => {function.instruction(&Instruction::I32ShrS); Type::Int},
+                    TokenType::ShiftRightUnsigned => {function.instruction(&Instruction::I32ShrU); Type::Int},
+                    _ => {self.error("undefined operation between 2 integers", Some(line)); Type::Bool},
+                }
+            }
+            (Type::Uint, Type::Uint) => {
                 match operator {
-                    TokenType::Plus => {function.instruction(&Instruction::F64Add); Value::Float(0.0)},
-                    TokenType::Minus => {function.instruction(&Instruction::F64Sub); Value::Float(0.0)},
-                    TokenType::Star => {function.instruction(&Instruction::F64Mul); Value::Float(0.0)},
-                    TokenType::Slash => {function.instruction(&Instruction::F64Div); Value::Float(0.0)},
-                    TokenType::EqualEqual => {function.instruction(&Instruction::F64Eq); Value::Bool(true)},
-                    TokenType::BangEqual => {function.instruction(&Instruction::F64Ne); Value::Bool(true)},
-                    TokenType::Less => {function.instruction(&Instruction::F64Lt); Value::Bool(true)},
-                    TokenType::LessEqual => {function.instruction(&Instruction::F64Le); Value::Bool(true)},
-                    TokenType::Greater => {function.instruction(&Instruction::F64Gt); Value::Bool(true)},
-                    TokenType::GreaterEqual => {function.instruction(&Instruction::F64Ge); Value::Bool(true)},
-                    _ => {self.error("undefined operation between 2 floats", Some(line)); Value::Bool(true)},
+                    TokenType::Plus => {function.instruction(&Instruction::I32Add); Type::Uint},
+                    TokenType::Minus => {function.instruction(&Instruction::I32Sub); Type::Uint},
+                    TokenType::Star => {function.instruction(&Instruction::I32Mul); Type::Uint},
+                    TokenType::Slash => {function.instruction(&Instruction::I32DivU); Type::Uint},
+                    TokenType::EqualEqual => {function.instruction(&Instruction::I32Eq); Type::Bool},
+                    TokenType::BangEqual => {function.instruction(&Instruction::I32Ne); Type::Bool},
+                    TokenType::Less => {function.instruction(&Instruction::I32LtU); Type::Bool},
+                    TokenType::LessEqual => {function.instruction(&Instruction::I32LeU); Type::Bool},
+                    TokenType::Greater => {function.instruction(&Instruction::I32GtU); Type::Bool},
+                    TokenType::GreaterEqual => {function.instruction(&Instruction::I32GeU); Type::Bool},
+                    TokenType::Modulo => {function.instruction(&Instruction::I32RemU); Type::Uint},
+                    TokenType::BitAnd => {function.instruction(&Instruction::I32And); Type::Uint},
+                    TokenType::BitOr => {function.instruction(&Instruction::I32Or); Type::Uint},
+                    TokenType::BitXor => {function.instruction(&Instruction::I32Xor); Type::Uint},
+                    TokenType::ShiftLeft => {function.instruction(&Instruction::I32Shl); Type::Uint},
+                    //uints have no sign bit, so both right shifts are logical
+                    TokenType::ShiftRight | TokenType::ShiftRightUnsigned => {function.instruction(&Instruction::I32ShrU); Type::Uint},
+                    _ => {self.error("undefined operation between 2 uints", Some(line)); Type::Bool},
                 }
             }
-            (Value::Index(s), Value::Index(s1)) => {
-                return Value::Index(self.add_strings(*s, *s1, line));
+            (Type::Float, Type::Float) => {
+                match operator {
+                    TokenType::Plus => {function.instruction(&Instruction::F64Add); Type::Float},
+                    TokenType::Minus => {function.instruction(&Instruction::F64Sub); Type::Float},
+                    TokenType::Star => {function.instruction(&Instruction::F64Mul); Type::Float},
+                    TokenType::Slash => {function.instruction(&Instruction::F64Div); Type::Float},
+                    TokenType::EqualEqual => {function.instruction(&Instruction::F64Eq); Type::Bool},
+                    TokenType::BangEqual => {function.instruction(&Instruction::F64Ne); Type::Bool},
+                    TokenType::Less => {function.instruction(&Instruction::F64Lt); Type::Bool},
+                    TokenType::LessEqual => {function.instruction(&Instruction::F64Le); Type::Bool},
+                    TokenType::Greater => {function.instruction(&Instruction::F64Gt); Type::Bool},
+                    TokenType::GreaterEqual => {function.instruction(&Instruction::F64Ge); Type::Bool},
+                    _ => {self.error("undefined operation between 2 floats", Some(line)); Type::Bool},
+                }
             }
-            (Value::Int(_), _) => {
-                {self.error("Cannot execute this operation on different types, use 2 ints", Some(line)); Value::Bool(true)}
+            (Type::String, Type::String) => {
+                match operator {
+                    TokenType::Plus => {
+                        let concat = self.helper(Helper::Concat);
+                        function.instruction(&Instruction::Call(concat));
+                        Type::String
+                    },
+                    //the comparison gives a negative number, 0 or a positive number
+                    TokenType::EqualEqual | TokenType::BangEqual | TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual => {
+                        let compare = self.helper(Helper::StrCompare);
+                        function.instruction(&Instruction::Call(compare));
+                        function.instruction(&Instruction::I32Const(0));
+                        function.instruction(&match operator {
+                            TokenType::EqualEqual => Instruction::I32Eq,
+                            TokenType::BangEqual => Instruction::I32Ne,
+                            TokenType::Less => Instruction::I32LtS,
+                            TokenType::LessEqual => Instruction::I32LeS,
+                            TokenType::Greater => Instruction::I32GtS,
+                            _ => Instruction::I32GeS,
+                        });
+                        Type::Bool
+                    },
+                    _ => {self.error("undefined operation between 2 strings", Some(line)); Type::Bool},
+                }
+            }
+            (Type::String, _) => {
+                {self.error("Cannot execute this operation on different types, use 2 strings", Some(line)); Type::Bool}
             }
-            (Value::Float(_), _) => {
-                {self.error("Cannot execute this operation on different types, use 2 floats", Some(line)); Value::Bool(true)}
+            (Type::Int, _) => {
+                {self.error("Cannot execute this operation on different types, use 2 ints", Some(line)); Type::Bool}
             }
-            (Value::Bool(_), Value::Bool(_)) => {
+            (Type::Uint, _) => {
+                {self.error("Cannot execute this operation on different types, use 2 uints", Some(line)); Type::Bool}
+            }
+            (Type::Long, Type::Long) => {
                 match operator {
-                    TokenType::EqualEqual => {function.instruction(&Instruction::I32Eq); Value::Bool(true)},
-                    TokenType::BangEqual => {function.instruction(&Instruction::I32Ne); Value::Bool(true)},
-                    TokenType::And => {function.instruction(&Instruction::I32And); Value::Bool(true)},
-                    TokenType::Or => {function.instruction(&Instruction::I32Or); Value::Bool(true)},
-                    _ => {self.error("undefined operation between 2 booleans", Some(line)); Value::Bool(true)}
+                    TokenType::Plus => {function.instruction(&Instruction::I64Add); Type::Long},
+                    TokenType::Minus => {function.instruction(&Instruction::I64Sub); Type::Long},
+                    TokenType::Star => {function.instruction(&Instruction::I64Mul); Type::Long},
+                    TokenType::Slash => {function.instruction(&Instruction::I64DivS); Type::Long},
+                    TokenType::EqualEqual => {function.instruction(&Instruction::I64Eq); Type::Bool},
+                    TokenType::BangEqual => {function.instruction(&Instruction::I64Ne); Type::Bool},
+                    TokenType::Less => {function.instruction(&Instruction::I64LtS); Type::Bool},
+                    TokenType::LessEqual => {function.instruction(&Instruction::I64LeS); Type::Bool},
+                    TokenType::Greater => {function.instruction(&Instruction::I64GtS); Type::Bool},
+                    TokenType::GreaterEqual => {function.instruction(&Instruction::I64GeS); Type::Bool},
+                    TokenType::Modulo => {function.instruction(&Instruction::I64RemS); Type::Long},
+                    TokenType::BitAnd => {function.instruction(&Instruction::I64And); Type::Long},
+                    TokenType::BitOr => {function.instruction(&Instruction::I64Or); Type::Long},
+                    TokenType::BitXor => {function.instruction(&Instruction::I64Xor); Type::Long},
+                    TokenType::ShiftLeft => {function.instruction(&Instruction::I64Shl); Type::Long},
+                    TokenType::ShiftRight => {function.instruction(&Instruction::I64ShrS); Type::Long},
+                    TokenType::ShiftRightUnsigned => {function.instruction(&Instruction::I64ShrU); Type::Long},
+                    _ => {self.error("undefined operation between 2 longs", Some(line)); Type::Bool},
                 }
             }
-            (Value::Bool(_), _) => {
-                {self.error("Cannot execute this operation on different types, use 2 booleans", Some(line)); Value::Bool(true)}
+            (Type::Long, _) => {
+                {self.error("Cannot execute this operation on different types, use 2 longs", Some(line)); Type::Bool}
+            }
+            (Type::Float, _) => {
+                {self.error("Cannot execute this operation on different types, use 2 floats", Some(line)); Type::Bool}
             }
-            _ => {self.error(format!("undefined operation {:?}", operator).as_str(), Some(line)); Value::Bool(true)}
+            (Type::Bool, Type::Bool) => {
+                match operator {
+                    TokenType::EqualEqual => {function.instruction(&Instruction::I32Eq); Type::Bool},
+                    TokenType::BangEqual => {function.instruction(&Instruction::I32Ne); Type::Bool},
+                    //& and | on booleans always evaluate both sides
+                    TokenType::BitAnd => {function.instruction(&Instruction::I32And); Type::Bool},
+                    TokenType::BitOr => {function.instruction(&Instruction::I32Or); Type::Bool},
+                    TokenType::BitXor => {function.instruction(&Instruction::I32Xor); Type::Bool},
+                    _ => {self.error("undefined operation between 2 booleans", Some(line)); Type::Bool}
+                }
+            }
+            (Type::Bool, _) => {
+                {self.error("Cannot execute this operation on different types, use 2 booleans", Some(line)); Type::Bool}
+            }
+            (Type::Enum(a), Type::Enum(b)) if a == b => {
+                match operator {
+                    TokenType::EqualEqual => {function.instruction(&Instruction::I32Eq); Type::Bool},
+                    TokenType::BangEqual => {function.instruction(&Instruction::I32Ne); Type::Bool},
+                    _ => {self.error("enums can only be compared with == and !=", Some(line)); Type::Bool}
+                }
+            }
+            _ => {self.error(format!("undefined operation {:?} between {:?} and {:?}", operator, t1, t2).as_str(), Some(line)); Type::Bool}
+        }
+    }
+
+    fn cast(&self, function: &mut Function, from: &Type, to: &Type, line: usize) {
+        //float to integer casts saturate instead of trapping
+        let instructions: &[Instruction] = match (from, to) {
+            (a, b) if a == b => &[],
+            (Type::Int, Type::Uint) | (Type::Uint, Type::Int) | (Type::Bool, Type::Int) | (Type::Bool, Type::Uint) => &[],
+            (Type::Int, Type::Float) => &[Instruction::F64ConvertI32S],
+            (Type::Uint | Type::Bool, Type::Float) => &[Instruction::F64ConvertI32U],
+            (Type::Long, Type::Float) => &[Instruction::F64ConvertI64S],
+            (Type::Float, Type::Int) => &[Instruction::I32TruncSatF64S],
+            (Type::Float, Type::Uint) => &[Instruction::I32TruncSatF64U],
+            (Type::Float, Type::Long) => &[Instruction::I64TruncSatF64S],
+            (Type::Int, Type::Long) => &[Instruction::I64ExtendI32S],
+            (Type::Uint | Type::Bool, Type::Long) => &[Instruction::I64ExtendI32U],
+            (Type::Long, Type::Int | Type::Uint) => &[Instruction::I32WrapI64],
+            //anything that is not zero is true
+            (Type::Int | Type::Uint, Type::Bool) => &[Instruction::I32Eqz, Instruction::I32Eqz],
+            (Type::Long, Type::Bool) => &[Instruction::I64Eqz, Instruction::I32Eqz],
+            (Type::Float, Type::Bool) => &[Instruction::F64Const(0.0), Instruction::F64Ne],
+            _ => {self.error(format!("cannot cast {:?} to {:?}", from, to).as_str(), Some(line)); &[]},
+        };
+        for instruction in instructions {
+            function.instruction(instruction);
         }
     }
 
-    fn unary(&self, function: &mut Function, t1: &Value, operator: TokenType, line: usize) {
+    fn unary(&self, function: &mut Function, t1: &Type, operator: TokenType, line: usize) {
         match t1 {
-            Value::Int(_) => {
+            Type::Int => {
                 match operator {
                     TokenType::Minus => {
+                        //-x is ~x + 1 in two's complement
+                        function.instruction(&Instruction::I32Const(-1));
+                        function.instruction(&Instruction::I32Xor);
+                        function.instruction(&Instruction::I32Const(1));
+                        function.instruction(&Instruction::I32Add);
+                    },
+                    TokenType::BitNot => {
                         function.instruction(&Instruction::I32Const(-1));
-                        function.instruction(&Instruction::I32Mul);
+                        function.instruction(&Instruction::I32Xor);
                     },
                     _ => self.error("undefined unary operation for type int", Some(line)),
                 };
             }
-            Value::Float(_) => {
+            Type::Uint => {
+                match operator {
+                    TokenType::BitNot => {
+                        function.instruction(&Instruction::I32Const(-1));
+                        function.instruction(&Instruction::I32Xor);
+                    },
+                    _ => self.error("undefined unary operation for type uint", Some(line)),
+                };
+            }
+            Type::Long => {
+                match operator {
+                    TokenType::Minus => {
+                        function.instruction(&Instruction::I64Const(-1));
+                        function.instruction(&Instruction::I64Xor);
+                        function.instruction(&Instruction::I64Const(1));
+                        function.instruction(&Instruction::I64Add);
+                    },
+                    TokenType::BitNot => {
+                        function.instruction(&Instruction::I64Const(-1));
+                        function.instruction(&Instruction::I64Xor);
+                    },
+                    _ => self.error("undefined unary operation for type long", Some(line)),
+                };
+            }
+            Type::Float => {
                 match operator {
                     TokenType::Minus => {function.instruction(&Instruction::F64Neg);},
                     _ => self.error("undefined unary operation for type float", Some(line)),
                 };
             }
-            Value::Bool(_) => {
+            Type::Bool => {
                 match operator {
                     TokenType::Bang => {function.instruction(&Instruction::I32Eqz);},
                     _ => self.error("undefined unary operation for type boolean", Some(line)),
@@ -686,43 +1814,221 @@ impl Compiler {
         }
     }
 
-    fn add_strings(&mut self, t1: i32, t2: i32, line: usize) -> i32 {
-        //takes 2 indexes to strings and return an index to the new string
-        self.offsets.get(&t1).unwrap_or_else(|| {
-            self.error("undefined string", Some(line));
-            std::process::exit(0);
-        });
-        self.offsets.get(&t2).unwrap_or_else(|| {
-            self.error("undefined string", Some(line));
-            std::process::exit(0);
-        });
-        let mut s = String::new();
-        for &t in &[t1, t2] {
-            if let Some(offset) = self.offsets.get(&t) {
-                for &c in &self.strings[t as usize..(t + offset) as usize] {
-                    s.push(c as char);
+    pub fn make_string(&mut self, s: String) -> i32 {
+        //stores a static string as [length: i32][bytes] and returns its offset
+        if let Some(&offset) = self.interned.get(&s) {
+            return offset;
+        }
+        let offset = self.static_record(&[&(s.len() as i32).to_le_bytes(), s.as_bytes()].concat());
+        self.interned.insert(s, offset);
+        offset
+    }
+
+    fn static_record(&mut self, bytes: &[u8]) -> i32 {
+        //appends data to the static memory, 4 byte aligned, and returns its offset
+        while !self.strings.len().is_multiple_of(4) {
+            self.strings.push(0);
+        }
+        let offset = self.strings.len() as i32;
+        self.strings.extend_from_slice(bytes);
+        offset
+    }
+
+    fn function_record(&mut self, slot: i32) -> i32 {
+        //the [table slot][environment] record of a function value without an environment
+        self.static_record(&[slot.to_le_bytes(), 0i32.to_le_bytes()].concat())
+    }
+
+    fn signature(&mut self, params: Vec<ValType>, results: Vec<ValType>) -> u32 {
+        //the type index of a signature, functions with the same signature share a type
+        let signature = (params, results);
+        match self.signatures.iter().position(|s| *s == signature) {
+            Some(index) => index as u32,
+            None => {
+                self.signatures.push(signature);
+                self.signatures.len() as u32 - 1
+            }
+        }
+    }
+
+    fn fn_signature(&mut self, params: &[Type], return_type: &Type, environment: bool) -> u32 {
+        //functions in the table take the environment of the function value as their last parameter
+        let mut params1: Vec<ValType> = params.iter().map(|p| self.val_type(p, None)).collect();
+        if environment {
+            params1.push(ValType::I32);
+        }
+        let results1 = match return_type {
+            Type::Void => vec![],
+            Type::Tuple(types) => types.iter().map(|t| self.val_type(t, None)).collect(), //wasm multi-value
+            _ => vec![self.val_type(return_type, None)],
+        };
+        self.signature(params1, results1)
+    }
+
+    fn function_value(&mut self, function: &mut Function, name: &str) -> Type {
+        //a named function goes in the table through a thunk that drops the environment, its value is a static record
+        let (index, params, return_type) = self.funcs[name].clone();
+        let t = Type::Func(params.clone(), Box::new(return_type.clone()));
+        let offset = match self.func_values.get(&index) {
+            Some(&offset) => offset,
+            None => {
+                let mut thunk = Function::new(vec![]);
+                for i in 0..params.len() as u32 {
+                    thunk.instruction(&Instruction::LocalGet(i));
+                }
+                thunk.instruction(&Instruction::Call(index));
+                thunk.instruction(&Instruction::End);
+                let type_index = self.fn_signature(&params, &return_type, true);
+                self.table.push((thunk, type_index));
+                let offset = self.function_record(self.table.len() as i32);
+                self.func_values.insert(index, offset);
+                offset
+            }
+        };
+        function.instruction(&Instruction::I32Const(offset));
+        t
+    }
+
+    fn lambda(&mut self, function: &mut Function, params: Vec<(Type, Token)>, body: Expr, line: usize) -> Type {
+        //the lambda becomes a table function taking (params, environment), the environment holds the captured locals
+        let bound: HashSet<String> = params.iter().map(|p| p.1.literal.clone().unwrap().as_str()).collect();
+        let mut names = vec![];
+        Self::free_vars(&body, &bound, &mut names);
+        let mut captures: Vec<(String, u32, Type, u64)> = vec![]; //name, local index outside, type, offset in the environment
+        let mut size: u64 = 0;
+        for name in names {
+            if let Some((true, index, t)) = self.find_var(&name) {
+                let align = Self::size_of(&t) as u64;
+                let offset = size.next_multiple_of(align);
+                size = offset + align;
+                captures.push((name, index, t, offset));
+            }
+        }
+        //the body is compiled with its own locals: the parameters, the environment, the captured values and the scratch
+        let saved = (
+            std::mem::take(&mut self.scopes),
+            self.locals_count,
+            std::mem::take(&mut self.declared),
+            std::mem::take(&mut self.ended),
+            std::mem::take(&mut self.control),
+            self.scratch,
+        );
+        let mut locals: Vec<(u32, ValType)> = captures.iter().map(|c| (1, self.val_type(&c.2, Some(line)))).collect();
+        locals.push((1, ValType::I32));
+        let mut f = Function::new(locals);
+        self.begin_function(&params, &[]);
+        let environment = self.locals_count;
+        self.locals_count += 1;
+        for (name, _, t, offset) in &captures {
+            f.instruction(&Instruction::LocalGet(environment));
+            self.load(&mut f, t, *offset);
+            f.instruction(&Instruction::LocalSet(self.locals_count));
+            self.scopes[0].insert(name.clone(), (self.locals_count, t.clone()));
+            self.locals_count += 1;
+        }
+        self.scratch = self.locals_count;
+        let return_type = self.compile_expr(&mut f, body);
+        f.instruction(&Instruction::End);
+        (self.scopes, self.locals_count, self.declared, self.ended, self.control, self.scratch) = saved;
+        let param_types: Vec<Type> = params.into_iter().map(|p| p.0).collect();
+        let type_index = self.fn_signature(&param_types, &return_type, true);
+        self.table.push((f, type_index));
+        let slot = self.table.len() as i32;
+        if captures.is_empty() {
+            //nothing to capture, the value is a static record like the one of a named function
+            let offset = self.function_record(slot);
+            function.instruction(&Instruction::I32Const(offset));
+        } else {
+            //[slot][pointer to the environment][environment]
+            let alloc = self.helper(Helper::Alloc);
+            function.instruction(&Instruction::I32Const(8 + size as i32));
+            function.instruction(&Instruction::Call(alloc));
+            function.instruction(&Instruction::LocalSet(self.scratch));
+            function.instruction(&Instruction::LocalGet(self.scratch));
+            function.instruction(&Instruction::I32Const(slot));
+            function.instruction(&Instruction::I32Store(mem(2)));
+            function.instruction(&Instruction::LocalGet(self.scratch));
+            function.instruction(&Instruction::LocalGet(self.scratch));
+            function.instruction(&Instruction::I32Const(8));
+            function.instruction(&Instruction::I32Add);
+            function.instruction(&Instruction::I32Store(MemArg {offset: 4, ..mem(2)}));
+            for (_, index, t, offset) in &captures {
+                function.instruction(&Instruction::LocalGet(self.scratch));
+                function.instruction(&Instruction::LocalGet(*index));
+                self.store(function, t, 8 + offset);
+            }
+            function.instruction(&Instruction::LocalGet(self.scratch));
+        }
+        Type::Func(param_types, Box::new(return_type))
+    }
+
+    fn free_vars(expr: &Expr, bound: &HashSet<String>, names: &mut Vec<String>) {
+        //the variables an expression uses that are not bound by a lambda around it, in order of first use
+        let mut visit = |e: &Expr| Self::free_vars(e, bound, names);
+        match expr {
+            Expr::Variable {name, ..} | Expr::Assign {name, ..} => {
+                let name = name.literal.clone().unwrap().as_str();
+                if let Expr::Assign {value, ..} = expr {
+                    Self::free_vars(value, bound, names);
+                }
+                if !bound.contains(&name) && !names.contains(&name) {
+                    names.push(name);
                 }
             }
+            Expr::Binary {left, right, ..} => {visit(left); visit(right);},
+            Expr::Call {callee, arguments, ..} => {
+                visit(callee);
+                arguments.iter().for_each(visit);
+            }
+            Expr::Grouping(e) | Expr::Unary {expression: e, ..} | Expr::Cast {value: e, ..} | Expr::NewArray {size: e, ..} | Expr::Get {object: e, ..} => visit(e),
+            Expr::Index {array, index, ..} => {visit(array); visit(index);},
+            Expr::SetIndex {array, index, value, ..} => {visit(array); visit(index); visit(value);},
+            Expr::Set {object, value, ..} => {visit(object); visit(value);},
+            Expr::Ternary {condition, then_branch, else_branch, ..} => {visit(condition); visit(then_branch); visit(else_branch);},
+            Expr::Lambda {params, body, ..} => {
+                let mut inner = bound.clone();
+                inner.extend(params.iter().map(|p| p.1.literal.clone().unwrap().as_str()));
+                Self::free_vars(body, &inner, names);
+            }
+            Expr::Tuple {values, ..} => values.iter().for_each(visit),
+            Expr::Literal {..} => {},
         }
-        self.make_string(s)
     }
 
-    fn make_string(&mut self, s: String) -> i32 {
-        let offset = self.strings.len();
-        let b = s.as_bytes();
-        self.offsets.insert(offset as i32, b.len() as i32);
-        self.strings.extend_from_slice(b);
-        offset as i32
+    fn call_indirect(&mut self, function: &mut Function, callee: &Token, params: &[Type], return_type: Type, arguments: Vec<Expr>, line: usize) -> Type {
+        //arguments, then the environment and the slot from the [slot][environment] record of the value
+        let name = callee.literal.clone().unwrap().as_str();
+        if params.len() != arguments.len() {
+            self.error(format!("function value \"{}\" takes {} arguments, but {} were given", name, params.len(), arguments.len()).as_str(), Some(line));
+        }
+        for (arg, param) in arguments.into_iter().zip(params) {
+            let t = self.compile_expr(function, arg);
+            if t != *param {
+                self.error(format!("cannot pass {:?} to a parameter of type {:?} in function value \"{}\"", t, param, name).as_str(), Some(line));
+            }
+        }
+        self.load_var(function, callee, line);
+        function.instruction(&Instruction::I32Load(MemArg {offset: 4, ..mem(2)}));
+        self.load_var(function, callee, line);
+        function.instruction(&Instruction::I32Load(mem(2)));
+        let ty = self.fn_signature(params, &return_type, true);
+        function.instruction(&Instruction::CallIndirect {ty, table: 0});
+        self.uses_table = true;
+        return_type
     }
 
-    fn print_wasm(&mut self, f: &mut Function, offset: i32, line: usize) {
-        let length = self.offsets.get(&offset).unwrap_or_else(|| {
-            self.error("undefined string", Some(line));
-            std::process::exit(0);
+    pub fn helper(&mut self, helper: Helper) -> u32 {
+        //returns the function index of a runtime helper, adding it to the module if needed
+        let position = self.helpers.iter().position(|&h| h == helper).unwrap_or_else(|| {
+            self.helpers.push(helper);
+            self.helpers.len() - 1
         });
-        f.instruction(&Instruction::I32Const(offset));
-        f.instruction(&Instruction::I32Const(*length));
-        f.instruction(&Instruction::Call(0));
+        self.helper_base + position as u32
+    }
+
+    fn set_origin(&mut self, name: &str) {
+        //errors about a declaration report the file it comes from
+        self.filename = self.origins.get(name).cloned().unwrap_or_else(|| self.main_filename.clone());
     }
 
     fn error(&self, msg: &str, line: Option<usize>) {
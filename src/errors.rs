@@ -17,16 +17,16 @@ impl KeyScriptError {
         else {
             print!("{} ",format!("[{}]", error_type.print()).yellow());
         }
-        if msg.is_some() {
-            print!("{} ", msg.unwrap().red());
+        if let Some(msg) = msg {
+            print!("{} ", msg.red());
         } else {
             print!("{} ", "unknown error".red());
         }
-        if line.is_some() {
-            print!("{} ", format!("{} line {}", "at".red(), line.unwrap()).cyan());
+        if let Some(line) = line {
+            print!("{} ", format!("{} line {}", "at".red(), line).cyan());
         }
-        if filename.is_some() {
-            println!("{}", format!("{} {}", "in file".red(), filename.unwrap()).cyan());
+        if let Some(filename) = filename {
+            println!("{}", format!("{} {}", "in file".red(), filename).cyan());
         } else {
             println!();
         }
@@ -0,0 +1,487 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{Expr, Stmt, Type};
+use crate::errors::KeyScriptError;
+use crate::scanner::{Token, TokenType, Value};
+
+//constant folding: replaces constants with their values and evaluates operations on literals at compile time
+//it also resolves the names of functions, which are prefixed with the namespace of their file
+pub struct Folder<'a> {
+    pub consts: HashMap<String, Value>,
+    namespace: Option<String>, //None for the main file
+    functions: HashSet<String>, //the functions declared in this file
+    locals: HashSet<String>, //the parameters and variables of the current function, they hide functions
+    pub imports: HashMap<String, (HashSet<String>, HashMap<String, Value>)>, //namespace, functions, constants
+    filename: &'a str,
+}
+
+impl<'a> Folder<'a> {
+    pub fn new(filename: &'a str, namespace: Option<String>) -> Folder<'a> {
+        Folder {
+            consts: HashMap::new(),
+            namespace,
+            functions: HashSet::new(),
+            locals: HashSet::new(),
+            imports: HashMap::new(),
+            filename,
+        }
+    }
+
+    pub fn fold(&mut self, code: Vec<Stmt>) -> Vec<Stmt> {
+        for stmt in &code {
+            if let Stmt::Fn {name, ..} = stmt {
+                self.functions.insert(name.clone());
+            }
+        }
+        //constants are visible everywhere (functions come first in the code), so they are collected first
+        let mut stmts = vec![];
+        for stmt in code {
+            match stmt {
+                Stmt::Const {name, value, t, line} => self.declare_const(name, value, t, line),
+                _ => stmts.push(stmt),
+            }
+        }
+        stmts.into_iter().map(|stmt| self.fold_stmt(stmt)).collect()
+    }
+
+    fn declare_const(&mut self, name: Token, value: Expr, t: Type, line: usize) {
+        let name = name.literal.unwrap().as_str();
+        if self.consts.contains_key(&name) {
+            self.error(format!("constant \"{}\" already declared", name).as_str(), line);
+        }
+        let value = match self.fold_expr(value) {
+            Expr::Literal{val} => val,
+            _ => {self.error(format!("the value of constant \"{}\" must be known at compile time", name).as_str(), line); std::process::exit(0);},
+        };
+        if Self::type_of(&value) != t {
+            self.error(format!("type mismatch, cannot assign {:?} to constant of type {:?}", Self::type_of(&value), t).as_str(), line);
+        }
+        self.consts.insert(name, value);
+    }
+
+    fn check_name(&self, name: &Token, line: usize) {
+        //variables cannot hide constants
+        let name = name.literal.clone().unwrap().as_str();
+        if self.consts.contains_key(&name) {
+            self.error(format!("\"{}\" is already declared as a constant", name).as_str(), line);
+        }
+        if self.imports.contains_key(&name) {
+            self.error(format!("\"{}\" is the name of an imported file", name).as_str(), line);
+        }
+    }
+
+    fn qualify(&self, name: String) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}.{}", namespace, name),
+            None => name,
+        }
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Print {expr, line} => Stmt::Print {expr: self.fold_print(expr), line},
+            Stmt::Block {stmts, vars} => Stmt::Block {
+                stmts: stmts.into_iter().map(|s| self.fold_stmt(s)).collect(),
+                vars,
+            },
+            Stmt::Expression(expr) => Stmt::Expression(self.fold_expr(expr)),
+            Stmt::If {condition, then_branch, else_branch, line} => Stmt::If {
+                condition: self.fold_expr(condition),
+                then_branch: Box::new(self.fold_stmt(*then_branch)),
+                else_branch: else_branch.map(|s| Box::new(self.fold_stmt(*s))),
+                line,
+            },
+            Stmt::Var {name, value, t, line} => {
+                self.check_name(&name, line);
+                self.locals.insert(name.literal.clone().unwrap().as_str());
+                Stmt::Var {name, value: value.map(|v| self.fold_expr(v)), t, line}
+            }
+            Stmt::Destructure {targets, value, line} => {
+                for (_, name) in &targets {
+                    self.check_name(name, line);
+                    self.locals.insert(name.literal.clone().unwrap().as_str());
+                }
+                Stmt::Destructure {targets, value: self.fold_expr(value), line}
+            }
+            Stmt::Global {name, value, t, exported, line} => {
+                self.check_name(&name, line);
+                Stmt::Global {name, value: value.map(|v| self.fold_expr(v)), t, exported, line}
+            }
+            Stmt::While {condition, block, label, line} => Stmt::While {
+                condition: self.fold_expr(condition),
+                block: Box::new(self.fold_stmt(*block)),
+                label,
+                line,
+            },
+            Stmt::For {init, condition, increment, block, label, line} => Stmt::For {
+                init: init.map(|s| Box::new(self.fold_stmt(*s))),
+                condition: self.fold_expr(condition),
+                increment: increment.map(|e| self.fold_expr(e)),
+                block: Box::new(self.fold_stmt(*block)),
+                label,
+                line,
+            },
+            Stmt::Fn {name, params, body, return_type, line} => {
+                self.locals.clear();
+                for (_, param) in &params {
+                    self.check_name(param, line);
+                    self.locals.insert(param.literal.clone().unwrap().as_str());
+                }
+                Stmt::Fn {name: self.qualify(name), params, body: Box::new(self.fold_stmt(*body)), return_type, line}
+            }
+            Stmt::Match {value, arms, line} => Stmt::Match {
+                value: self.fold_expr(value),
+                arms: arms.into_iter().map(|(p, s)| (p, self.fold_stmt(s))).collect(),
+                line,
+            },
+            Stmt::Switch {value, cases, default, line} => Stmt::Switch {
+                value: self.fold_expr(value),
+                cases: cases.into_iter().map(|(values, s)| {
+                    (values.into_iter().map(|v| self.fold_expr(v)).collect(), self.fold_stmt(s))
+                }).collect(),
+                default: default.map(|s| Box::new(self.fold_stmt(*s))),
+                line,
+            },
+            Stmt::Return {returnee, return_type, line} => Stmt::Return {returnee: self.fold_expr(returnee), return_type, line},
+            Stmt::Const {line, ..} => {self.error("constants must be declared at the top level", line); std::process::exit(0);},
+            Stmt::Extern {..} | Stmt::Struct {..} | Stmt::Enum {..} | Stmt::Break {..} | Stmt::Continue {..} => stmt,
+        }
+    }
+
+    fn fold_print(&mut self, expr: Expr) -> Expr {
+        //the "+" of a print statement joins text, so only the parts are folded
+        match expr {
+            Expr::Binary {left, operator, right, line} if operator.tt == TokenType::Plus => Expr::Binary {
+                left: Box::new(self.fold_print(*left)),
+                operator,
+                right: Box::new(self.fold_print(*right)),
+                line,
+            },
+            _ => self.fold_expr(expr),
+        }
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Variable {name, line} => match self.consts.get(&name.literal.clone().unwrap().as_str()) {
+                Some(val) => Expr::Literal {val: val.clone()},
+                None => self.resolve_function(Expr::Variable {name, line}, line), //a function used as a value
+            },
+            Expr::Assign {name, value, line} => {
+                if self.consts.contains_key(&name.literal.clone().unwrap().as_str()) {
+                    self.error(format!("cannot assign to constant \"{}\"", name.literal.clone().unwrap().as_str()).as_str(), line);
+                }
+                Expr::Assign {name, value: Box::new(self.fold_expr(*value)), line}
+            }
+            Expr::Grouping(expr) => match self.fold_expr(*expr) {
+                Expr::Literal {val} => Expr::Literal {val},
+                expr => Expr::Grouping(Box::new(expr)),
+            },
+            Expr::Binary {left, operator, right, line} => {
+                let left = self.fold_expr(*left);
+                let right = self.fold_expr(*right);
+                if let (Expr::Literal {val: a}, Expr::Literal {val: b}) = (&left, &right) {
+                    if let Some(val) = self.binary(a, b, operator.tt, line) {
+                        return Expr::Literal {val};
+                    }
+                }
+                Expr::Binary {left: Box::new(left), operator, right: Box::new(right), line}
+            }
+            Expr::Unary {operator, expression, line} => {
+                let expression = self.fold_expr(*expression);
+                if let Expr::Literal {val} = &expression {
+                    if let Some(val) = Self::unary(val, operator.tt) {
+                        return Expr::Literal {val};
+                    }
+                }
+                Expr::Unary {operator, expression: Box::new(expression), line}
+            }
+            Expr::Ternary {condition, then_branch, else_branch, line} => {
+                let condition = self.fold_expr(*condition);
+                let then_branch = self.fold_expr(*then_branch);
+                let else_branch = self.fold_expr(*else_branch);
+                //only when both arms are literals of the same type, so the arms are still type checked
+                if let (Expr::Literal {val: Value::Bool(c)}, Expr::Literal {val: a}, Expr::Literal {val: b}) = (&condition, &then_branch, &else_branch) {
+                    if Self::type_of(a) == Self::type_of(b) {
+                        return Expr::Literal {val: if *c {a.clone()} else {b.clone()}};
+                    }
+                }
+                Expr::Ternary {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(else_branch),
+                    line,
+                }
+            }
+            Expr::Cast {t, value, line} => {
+                let value = self.fold_expr(*value);
+                if let Expr::Literal {val} = &value {
+                    if let Some(val) = Self::cast(val, &t) {
+                        return Expr::Literal {val};
+                    }
+                }
+                Expr::Cast {t, value: Box::new(value), line}
+            }
+            Expr::Call {callee, arguments, line} => Expr::Call {
+                callee: Box::new(self.resolve_function(*callee, line)),
+                arguments: arguments.into_iter().map(|a| self.fold_expr(a)).collect(),
+                line,
+            },
+            Expr::Index {array, index, line} => Expr::Index {
+                array: Box::new(self.fold_expr(*array)),
+                index: Box::new(self.fold_expr(*index)),
+                line,
+            },
+            Expr::SetIndex {array, index, value, operator, line} => Expr::SetIndex {
+                array: Box::new(self.fold_expr(*array)),
+                index: Box::new(self.fold_expr(*index)),
+                value: Box::new(self.fold_expr(*value)),
+                operator,
+                line,
+            },
+            Expr::Tuple {values, line} => Expr::Tuple {values: values.into_iter().map(|v| self.fold_expr(v)).collect(), line},
+            Expr::NewArray {t, size, line} => Expr::NewArray {t, size: Box::new(self.fold_expr(*size)), line},
+            Expr::Get {object, name, line} => {
+                //file.CONSTANT is a constant of an imported file, file.function a function value
+                if let Expr::Variable {name: namespace, ..} = &*object {
+                    let namespace = namespace.literal.clone().unwrap().as_str();
+                    if let Some((functions, consts)) = self.imports.get(&namespace) {
+                        let member = name.literal.clone().unwrap().as_str();
+                        if let Some(val) = consts.get(&member) {
+                            return Expr::Literal {val: val.clone()};
+                        }
+                        if !functions.contains(&member) {
+                            self.error(format!("file \"{}\" has no constant or function \"{}\"", namespace, member).as_str(), line);
+                        }
+                        let mut name = name;
+                        name.literal = Some(Value::String(format!("{}.{}", namespace, member)));
+                        return Expr::Variable {name, line};
+                    }
+                }
+                Expr::Get {object: Box::new(self.fold_expr(*object)), name, line}
+            }
+            Expr::Set {object, name, value, operator, line} => Expr::Set {
+                object: Box::new(self.fold_expr(*object)),
+                name,
+                value: Box::new(self.fold_expr(*value)),
+                operator,
+                line,
+            },
+            Expr::Lambda {params, body, line} => {
+                //the parameters only hide functions inside the body
+                let saved = self.locals.clone();
+                for (_, param) in &params {
+                    self.check_name(param, line);
+                    self.locals.insert(param.literal.clone().unwrap().as_str());
+                }
+                let body = self.fold_expr(*body);
+                self.locals = saved;
+                Expr::Lambda {params, body: Box::new(body), line}
+            }
+            Expr::Literal {..} => expr,
+        }
+    }
+
+    fn resolve_function(&self, callee: Expr, line: usize) -> Expr {
+        //the functions of this file get its namespace (unless a variable hides them), file.function has to name a function of an imported file
+        let mut name = match callee {
+            Expr::Variable {name, ..} => name,
+            _ => return callee,
+        };
+        let function = name.literal.clone().unwrap().as_str();
+        if let Some((namespace, f)) = function.split_once('.') {
+            match self.imports.get(namespace) {
+                Some((functions, _)) if !functions.contains(f) => {
+                    self.error(format!("file \"{}\" has no function \"{}\"", namespace, f).as_str(), line);
+                }
+                Some(_) => {},
+                None => self.error(format!("\"{}\" is not an imported file", namespace).as_str(), line),
+            }
+        } else if self.functions.contains(&function) && !self.locals.contains(&function) {
+            name.literal = Some(Value::String(self.qualify(function)));
+        }
+        Expr::Variable {name, line}
+    }
+
+    fn binary(&self, a: &Value, b: &Value, operator: TokenType, line: usize) -> Option<Value> {
+        //follows the wasm instructions the compiler would emit, anything else is left to the compiler
+        match (a, b) {
+            (Value::Int(a), Value::Int(b)) => {
+                let (a, b) = (*a, *b);
+                self.divisor(b == 0, operator, line);
+                Some(match operator {
+                    TokenType::Plus => Value::Int(a.wrapping_add(b)),
+                    TokenType::Minus => Value::Int(a.wrapping_sub(b)),
+                    TokenType::Star => Value::Int(a.wrapping_mul(b)),
+                    TokenType::Slash => Value::Int(self.checked(a.checked_div(b), line)),
+                    TokenType::Modulo => Value::Int(a.wrapping_rem(b)),
+                    TokenType::BitAnd => Value::Int(a & b),
+                    TokenType::BitOr => Value::Int(a | b),
+                    TokenType::BitXor => Value::Int(a ^ b),
+                    TokenType::ShiftLeft => Value::Int(a.wrapping_shl(b as u32)),
+                    TokenType::ShiftRight => Value::Int(a.wrapping_shr(b as u32)),
+                    TokenType::ShiftRightUnsigned => Value::Int((a as u32).wrapping_shr(b as u32) as i32),
+                    _ => Value::Bool(Self::compare(a.cmp(&b), operator)?),
+                })
+            }
+            (Value::Uint(a), Value::Uint(b)) => {
+                let (a, b) = (*a, *b);
+                self.divisor(b == 0, operator, line);
+                Some(match operator {
+                    TokenType::Plus => Value::Uint(a.wrapping_add(b)),
+                    TokenType::Minus => Value::Uint(a.wrapping_sub(b)),
+                    TokenType::Star => Value::Uint(a.wrapping_mul(b)),
+                    TokenType::Slash => Value::Uint(self.checked(a.checked_div(b), line)),
+                    TokenType::Modulo => Value::Uint(a.wrapping_rem(b)),
+                    TokenType::BitAnd => Value::Uint(a & b),
+                    TokenType::BitOr => Value::Uint(a | b),
+                    TokenType::BitXor => Value::Uint(a ^ b),
+                    TokenType::ShiftLeft => Value::Uint(a.wrapping_shl(b)),
+                    TokenType::ShiftRight | TokenType::ShiftRightUnsigned => Value::Uint(a.wrapping_shr(b)),
+                    _ => Value::Bool(Self::compare(a.cmp(&b), operator)?),
+                })
+            }
+            (Value::Long(a), Value::Long(b)) => {
+                let (a, b) = (*a, *b);
+                self.divisor(b == 0, operator, line);
+                Some(match operator {
+                    TokenType::Plus => Value::Long(a.wrapping_add(b)),
+                    TokenType::Minus => Value::Long(a.wrapping_sub(b)),
+                    TokenType::Star => Value::Long(a.wrapping_mul(b)),
+                    TokenType::Slash => Value::Long(self.checked(a.checked_div(b), line)),
+                    TokenType::Modulo => Value::Long(a.wrapping_rem(b)),
+                    TokenType::BitAnd => Value::Long(a & b),
+                    TokenType::BitOr => Value::Long(a | b),
+                    TokenType::BitXor => Value::Long(a ^ b),
+                    TokenType::ShiftLeft => Value::Long(a.wrapping_shl(b as u32)),
+                    TokenType::ShiftRight => Value::Long(a.wrapping_shr(b as u32)),
+                    TokenType::ShiftRightUnsigned => Value::Long((a as u64).wrapping_shr(b as u32) as i64),
+                    _ => Value::Bool(Self::compare(a.cmp(&b), operator)?),
+                })
+            }
+            (Value::Float(a), Value::Float(b)) => {
+                let (a, b) = (*a, *b);
+                Some(match operator {
+                    TokenType::Plus => Value::Float(a + b),
+                    TokenType::Minus => Value::Float(a - b),
+                    TokenType::Star => Value::Float(a * b),
+                    TokenType::Slash => Value::Float(a / b),
+                    TokenType::EqualEqual => Value::Bool(a == b),
+                    TokenType::BangEqual => Value::Bool(a != b),
+                    TokenType::Less => Value::Bool(a < b),
+                    TokenType::LessEqual => Value::Bool(a <= b),
+                    TokenType::Greater => Value::Bool(a > b),
+                    TokenType::GreaterEqual => Value::Bool(a >= b),
+                    _ => return None,
+                })
+            }
+            (Value::Bool(a), Value::Bool(b)) => {
+                let (a, b) = (*a, *b);
+                Some(Value::Bool(match operator {
+                    TokenType::EqualEqual => a == b,
+                    TokenType::BangEqual => a != b,
+                    TokenType::And | TokenType::BitAnd => a & b,
+                    TokenType::Or | TokenType::BitOr => a | b,
+                    TokenType::BitXor => a ^ b,
+                    _ => return None,
+                }))
+            }
+            (Value::String(a), Value::String(b)) if operator == TokenType::Plus => Some(Value::String(format!("{}{}", a, b))),
+            //strings compare their bytes, like the runtime helper
+            (Value::String(a), Value::String(b)) => Some(Value::Bool(Self::compare(a.as_bytes().cmp(b.as_bytes()), operator)?)),
+            _ => None,
+        }
+    }
+
+    fn compare(ordering: std::cmp::Ordering, operator: TokenType) -> Option<bool> {
+        match operator {
+            TokenType::EqualEqual => Some(ordering.is_eq()),
+            TokenType::BangEqual => Some(ordering.is_ne()),
+            TokenType::Less => Some(ordering.is_lt()),
+            TokenType::LessEqual => Some(ordering.is_le()),
+            TokenType::Greater => Some(ordering.is_gt()),
+            TokenType::GreaterEqual => Some(ordering.is_ge()),
+            _ => None,
+        }
+    }
+
+    fn divisor(&self, zero: bool, operator: TokenType, line: usize) {
+        //division by zero would trap at runtime
+        if zero && matches!(operator, TokenType::Slash | TokenType::Modulo) {
+            self.error("division by zero in a constant expression", line);
+            std::process::exit(0);
+        }
+    }
+
+    fn checked<T>(&self, value: Option<T>, line: usize) -> T {
+        //MIN / -1 overflows and traps at runtime, MIN % -1 is 0 like wasm's rem_s
+        value.unwrap_or_else(|| {
+            self.error("integer overflow in a constant expression", line);
+            std::process::exit(0);
+        })
+    }
+
+    fn unary(val: &Value, operator: TokenType) -> Option<Value> {
+        match (val, operator) {
+            (Value::Int(n), TokenType::Minus) => Some(Value::Int(n.wrapping_neg())),
+            (Value::Long(n), TokenType::Minus) => Some(Value::Long(n.wrapping_neg())),
+            (Value::Float(n), TokenType::Minus) => Some(Value::Float(-n)),
+            (Value::Int(n), TokenType::BitNot) => Some(Value::Int(!n)),
+            (Value::Uint(n), TokenType::BitNot) => Some(Value::Uint(!n)),
+            (Value::Long(n), TokenType::BitNot) => Some(Value::Long(!n)),
+            (Value::Bool(b), TokenType::Bang) => Some(Value::Bool(!b)),
+            _ => None,
+        }
+    }
+
+    fn cast(val: &Value, t: &Type) -> Option<Value> {
+        //rust's "as" saturates float to integer casts like i32.trunc_sat_f64_s
+        let val = match (val, t) {
+            (Value::Int(n), Type::Int) => Value::Int(*n),
+            (Value::Int(n), Type::Uint) => Value::Uint(*n as u32),
+            (Value::Int(n), Type::Long) => Value::Long(*n as i64),
+            (Value::Int(n), Type::Float) => Value::Float(*n as f64),
+            (Value::Int(n), Type::Bool) => Value::Bool(*n != 0),
+            (Value::Uint(n), Type::Int) => Value::Int(*n as i32),
+            (Value::Uint(n), Type::Uint) => Value::Uint(*n),
+            (Value::Uint(n), Type::Long) => Value::Long(*n as i64),
+            (Value::Uint(n), Type::Float) => Value::Float(*n as f64),
+            (Value::Uint(n), Type::Bool) => Value::Bool(*n != 0),
+            (Value::Long(n), Type::Int) => Value::Int(*n as i32),
+            (Value::Long(n), Type::Uint) => Value::Uint(*n as u32),
+            (Value::Long(n), Type::Long) => Value::Long(*n),
+            (Value::Long(n), Type::Float) => Value::Float(*n as f64),
+            (Value::Long(n), Type::Bool) => Value::Bool(*n != 0),
+            (Value::Float(n), Type::Int) => Value::Int(*n as i32),
+            (Value::Float(n), Type::Uint) => Value::Uint(*n as u32),
+            (Value::Float(n), Type::Long) => Value::Long(*n as i64),
+            (Value::Float(n), Type::Float) => Value::Float(*n),
+            (Value::Float(n), Type::Bool) => Value::Bool(*n != 0.0),
+            (Value::Bool(b), Type::Int) => Value::Int(*b as i32),
+            (Value::Bool(b), Type::Uint) => Value::Uint(*b as u32),
+            (Value::Bool(b), Type::Long) => Value::Long(*b as i64),
+            (Value::Bool(b), Type::Float) => Value::Float(*b as i32 as f64),
+            (Value::Bool(b), Type::Bool) => Value::Bool(*b),
+            _ => return None,
+        };
+        Some(val)
+    }
+
+    fn type_of(val: &Value) -> Type {
+        match val {
+            Value::Int(_) => Type::Int,
+            Value::Uint(_) => Type::Uint,
+            Value::Long(_) => Type::Long,
+            Value::Float(_) => Type::Float,
+            Value::Bool(_) => Type::Bool,
+            Value::String(_) => Type::String,
+        }
+    }
+
+    fn error(&self, msg: &str, line: usize) {
+        KeyScriptError::error(
+            KeyScriptError::CompilerError,
+            Some(msg),
+            Some(line),
+            Some(self.filename));
+    }
+}
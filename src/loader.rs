@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{canonicalize, read_to_string};
+use std::path::{Path, PathBuf};
+use crate::ast::{Stmt, Type};
+use crate::errors::KeyScriptError;
+use crate::folder::Folder;
+use crate::parser::Parser;
+use crate::scanner::{Scanner, Value};
+
+//a parsed file, the files importing it use its functions and constants through its namespace
+struct Module {
+    namespace: String,
+    filename: String,
+    functions: HashSet<String>,
+    consts: HashMap<String, Value>,
+    structs: Vec<String>, //including the ones of its imports, types are shared by every file
+    enums: Vec<String>,
+}
+
+//loads the main file and everything it imports, every file is parsed once
+pub struct Loader {
+    modules: HashMap<PathBuf, Module>,
+    stack: Vec<PathBuf>, //the files being loaded, an import of one of them is a cycle
+    decls: Vec<Stmt>, //the declarations of the imported files, dependencies first
+    pub origins: HashMap<String, String>, //function or type name, the file declaring it
+    root: PathBuf, //the directory of the main file, file names are shown relative to it
+}
+
+impl Loader {
+    pub fn new(main: &Path) -> Loader {
+        Loader {
+            modules: HashMap::new(),
+            stack: Vec::new(),
+            decls: Vec::new(),
+            origins: HashMap::new(),
+            root: canonicalize(main).ok().and_then(|p| p.parent().map(|p| p.to_path_buf())).unwrap_or_default(),
+        }
+    }
+
+    pub fn load_main(&mut self, path: &Path, filename: &str) -> (Vec<Stmt>, Vec<Type>) {
+        //returns the code of the whole program and the locals of main
+        let (mut code, vars) = self.parse(path, filename, None);
+        let mut program = std::mem::take(&mut self.decls);
+        program.append(&mut code);
+        (program, vars)
+    }
+
+    fn load(&mut self, path: &Path, importer: &str, line: usize) -> PathBuf {
+        let key = match canonicalize(path) {
+            Ok(key) => key,
+            Err(_) => {
+                Self::error(format!("cannot import \"{}\", the file does not exist", path.display()).as_str(), Some(line), importer);
+                std::process::exit(0);
+            }
+        };
+        if let Some(start) = self.stack.iter().position(|p| *p == key) {
+            let mut cycle: Vec<String> = self.stack[start..].iter().map(|p| self.display(p)).collect();
+            cycle.push(self.display(&key));
+            Self::error(format!("import cycle: {}", cycle.join(" -> ")).as_str(), Some(line), importer);
+        }
+        if !self.modules.contains_key(&key) {
+            let filename = self.display(&key);
+            let namespace = key.file_stem().unwrap().to_string_lossy().to_string();
+            if let Some(other) = self.modules.values().find(|m| m.namespace == namespace) {
+                Self::error(format!("cannot import \"{}\", the file \"{}\" has the same name", filename, other.filename).as_str(), Some(line), importer);
+            }
+            self.parse(&key, &filename, Some(namespace));
+        }
+        key
+    }
+
+    fn parse(&mut self, path: &Path, filename: &str, namespace: Option<String>) -> (Vec<Stmt>, Vec<Type>) {
+        self.stack.push(canonicalize(path).unwrap());
+        let source = read_to_string(path).expect("failed to read file");
+        let mut scanner = Scanner::new(&source, filename);
+        let tokens = scanner.scan_tokens();
+        let mut parser = Parser::new(tokens, filename);
+        let mut folder = Folder::new(filename, namespace.clone());
+        let dir = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        for (import, line) in parser.imports() {
+            //paths are relative to the importing file
+            let key = self.load(&dir.join(&import), filename, line);
+            let module = &self.modules[&key];
+            if folder.imports.contains_key(&module.namespace) {
+                Self::error(format!("\"{}\" is already imported", module.namespace).as_str(), Some(line), filename);
+            }
+            folder.imports.insert(module.namespace.clone(), (module.functions.clone(), module.consts.clone()));
+            for name in &module.structs {
+                if !parser.structs.contains(name) {
+                    parser.structs.push(name.clone());
+                }
+            }
+            for name in &module.enums {
+                if !parser.enums.contains(name) {
+                    parser.enums.push(name.clone());
+                }
+            }
+        }
+        let code = parser.parse();
+        let code = folder.fold(code);
+        for stmt in &code {
+            let (name, line) = match stmt {
+                Stmt::Fn {name, line, ..} | Stmt::Extern {name, line, ..} => (name, *line),
+                Stmt::Struct {name, line, ..} | Stmt::Enum {name, line, ..} => (name, *line),
+                _ => continue,
+            };
+            //the namespaces keep functions apart, but types and extern functions are shared by every file
+            if let Some(other) = self.origins.get(name) {
+                if other != filename {
+                    Self::error(format!("\"{}\" is already declared in \"{}\"", name, other).as_str(), Some(line), filename);
+                }
+            }
+            self.origins.insert(name.clone(), filename.to_string());
+        }
+        self.stack.pop();
+        let namespace = match namespace {
+            Some(namespace) => namespace,
+            None => return (code, parser.vars),
+        };
+        let mut functions = HashSet::new();
+        for stmt in code {
+            match stmt {
+                Stmt::Fn {ref name, ..} => {
+                    functions.insert(name.split_once('.').unwrap().1.to_string());
+                    self.decls.push(stmt);
+                }
+                Stmt::Extern {..} | Stmt::Struct {..} | Stmt::Enum {..} => self.decls.push(stmt),
+                _ => Self::error("an imported file can only declare functions, structs, enums, constants and extern functions", Self::line_of(&stmt), filename),
+            }
+        }
+        self.modules.insert(canonicalize(path).unwrap(), Module {
+            namespace,
+            filename: filename.to_string(),
+            functions,
+            consts: folder.consts,
+            structs: parser.structs,
+            enums: parser.enums,
+        });
+        (vec![], vec![])
+    }
+
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).display().to_string()
+    }
+
+    fn line_of(stmt: &Stmt) -> Option<usize> {
+        match stmt {
+            Stmt::Print {line, ..} | Stmt::If {line, ..} | Stmt::Var {line, ..} | Stmt::Destructure {line, ..} | Stmt::Global {line, ..}
+            | Stmt::While {line, ..} | Stmt::For {line, ..} | Stmt::Match {line, ..} | Stmt::Switch {line, ..} => Some(*line),
+            _ => None,
+        }
+    }
+
+    fn error(msg: &str, line: Option<usize>, filename: &str) {
+        KeyScriptError::error(
+            KeyScriptError::CompilerError,
+            Some(msg),
+            line,
+            Some(filename));
+    }
+}
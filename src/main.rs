@@ -3,8 +3,11 @@ mod scanner;
 mod parser;
 mod compiler;
 mod ast;
+mod folder;
+mod loader;
+mod runtime;
 use std::path::Path;
-use std::{env, fs::read_to_string, fs::metadata};
+use std::{env, fs::metadata};
 use std::io::Write;
 use crate::errors::KeyScriptError;
 
@@ -18,7 +21,6 @@ fn main() {
             None,
             None);
     }
-    let file_name;
     let mut is_wat = false;
     let mut js = false;
     if args.len() > 2 && args [2] == "debug" {
@@ -29,7 +31,7 @@ fn main() {
     if args.len() > 3 && args [3] == "gen" {
         js = true;
     }
-    if &args[1] == "init" {
+    let file_name = if &args[1] == "init" {
         let loop_code = r#"int fib(int n) {
     if n < 2 {
         return n;
@@ -85,7 +87,7 @@ fn main() {
             return WebAssembly.instantiate(bytes, imports)
         })
         .then(result => {
-            const returnValue = result.instance.exports.fib(40); // use BigInt for ints, use exports.<function name> for functions.
+            const returnValue = result.instance.exports.fib(40); // use BigInt for longs, use exports.<function name> for functions.
             if (returnValue) {
                 document.getElementById('output').textContent = `Function returned: ${returnValue}`;
             } else {
@@ -103,10 +105,10 @@ fn main() {
 
         let mut file = std::fs::File::create("index.html").unwrap();
         file.write_all(html_code.as_bytes()).expect("Failed to write HTML code to file");
-        file_name = "index.kys";
+        "index.kys"
     } else {
-        file_name = &args[1];
-    }
+        &args[1]
+    };
     let path = Path::new(&file_name);
     let main_file_name = path.file_name().unwrap().to_str().unwrap();
     if args.len() >= 2 {
@@ -125,14 +127,10 @@ fn main() {
                 None,
             );
         } else {
-            let source = read_to_string(path).expect("failed to read file");
-            let mut scanner = scanner::Scanner::new(&source, main_file_name);
-            let tokens = scanner.scan_tokens();
-            // for i in &tokens {
-            //     println!("{:?}", i);
-            // }
-            let mut parser = parser::Parser::new(tokens, main_file_name);
-            let mut comp = compiler::Compiler::new(parser.parse(), parser.vars, file_name, js);
+            //the main file and the files it imports are scanned, parsed and folded by the loader
+            let mut loader = loader::Loader::new(path);
+            let (code, vars) = loader.load_main(path, main_file_name);
+            let mut comp = compiler::Compiler::new(code, vars, file_name, loader.origins, js);
             // println!("{:?}", parser.parse());
             comp.compile(is_wat);
 
@@ -143,7 +141,5 @@ fn main() {
 //todo list:
 //release!
 //infinite loop detection
-//strings in loops (how tf do you use strings in wasm)
-//arrays
 //try to add keyscript compile to npm run
 //start implementing features
\ No newline at end of file
@@ -1,33 +1,54 @@
-use crate::ast::{Expr, Stmt};
+use crate::ast::{Expr, Stmt, Type};
 use crate::errors::KeyScriptError;
 use crate::scanner::{Token, TokenType, Value};
 pub struct Parser<'a> {
     pub tokens: Vec<Token>,
     current: usize,
     filename: &'a str,
-    pub vars: Vec<TokenType>,
-    pub return_type: TokenType,
+    pub vars: Vec<Type>,
+    pub return_type: Type,
+    loops: Vec<Option<String>>, //labels of the loops around the current statement
+    switches: usize, //how many switches are around the current statement, break can leave them
+    pub structs: Vec<String>, //names of the structs declared so far (or imported), they can be used as types
+    pub enums: Vec<String>, //names of the enums declared so far (or imported)
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: Vec<Token>, filename: &'a str) -> Parser {
+    pub fn new(tokens: Vec<Token>, filename: &'a str) -> Parser<'a> {
         Parser {
             tokens,
             current: 0,
             filename,
             vars: Vec::new(),
-            return_type: TokenType::Void,
+            return_type: Type::Void,
+            loops: Vec::new(),
+            switches: 0,
+            structs: Vec::new(),
+            enums: Vec::new(),
         }
     }
 
+    pub fn imports(&mut self) -> Vec<(String, usize)> {
+        //import "path.kys"; directives come before everything else, returns the paths and their lines
+        let mut imports = vec![];
+        while self.match_tokens(&[TokenType::Import]) {
+            let line = self.previous().line;
+            let path = match self.consume(TokenType::Value, "expected a file path string after import").literal.clone() {
+                Some(Value::String(s)) => s,
+                _ => {self.error("the path of an import must be a string"); std::process::exit(0);},
+            };
+            self.consume(TokenType::Semicolon, "expected \";\" after import");
+            imports.push((path, line));
+        }
+        imports
+    }
+
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut statements: Vec<Stmt> = Vec::new();
         while !self.is_at_end() {
             let decl = self.declaration();
             match decl {
-                Stmt::Fn { name, params, body, return_type, line } => {
-                    statements.insert(0, Stmt::Fn { name, params, body, return_type, line });
-                }
+                Stmt::Fn {..} | Stmt::Extern {..} | Stmt::Struct {..} | Stmt::Enum {..} => statements.insert(0, decl),
                 _ => statements.push(decl),
             }
         }
@@ -35,123 +56,491 @@ impl<'a> Parser<'a> {
     }
 
     fn declaration(&mut self) -> Stmt {
-        if self.match_tokens(&[TokenType::Bool, TokenType::Int, TokenType::Float, TokenType::String, TokenType::Void]) {
-            let t = self.previous().clone();
+        if self.match_tokens(&[TokenType::Struct]) {
+            return self.struct_decl();
+        }
+        if self.match_tokens(&[TokenType::Enum]) {
+            return self.enum_decl();
+        }
+        if self.match_tokens(&[TokenType::Export]) {
+            self.consume(TokenType::Global, "expected global after export");
+            return self.global_decl(true);
+        }
+        if self.match_tokens(&[TokenType::Global]) {
+            return self.global_decl(false);
+        }
+        if self.match_tokens(&[TokenType::Const]) {
+            return self.const_decl();
+        }
+        if self.match_tokens(&[TokenType::Extern]) {
+            return self.extern_decl();
+        }
+        if self.check(&TokenType::Import) {
+            self.error("imports must be at the top of the file");
+        }
+        let tuple = self.check(&TokenType::LeftParen) && self.is_tuple_type();
+        if tuple || self.match_tokens(&[TokenType::Void]) || self.match_type() {
+            let (t, size) = if tuple {(self.tuple_type(), None)} else {self.type_decl()};
             self.consume(TokenType::Identifier, "expected identifier after type declaration");
             let name = self.previous().clone();
             if self.match_tokens(&[TokenType::LeftParen]) {
-                if t.tt == TokenType::String {
-                    self.error("functions cannot return a string, use the main script or a void function");
-                }
                 if let Value::String(n) = name.literal.clone().unwrap() {
                     if n == "main" {
                         self.error("cant have a function called main, because the main script is called main");
                     }
                 }
-                return self.fn_decl(name, t.tt);
+                if size.is_some() {
+                    self.error("a function cannot return an array with a size, use type[]");
+                }
+                return self.fn_decl(name, t);
             } else {
-                if self.match_tokens(&[TokenType::Void]) {
+                if t == Type::Void {
                     self.error("cannot have a variable of type void");
                 }
-                self.vars.push(t.tt);
-                return self.var_decl(name, t)
+                if self.check(&TokenType::Comma) {
+                    let stmt = self.destructure(name, t, size);
+                    self.vars.extend(Self::nested_vars(&stmt));
+                    return stmt;
+                }
+                self.vars.push(t.clone());
+                return self.var_decl(name, t, size)
             }
         }
-        self.statement(-1)
+        let stmt = self.statement();
+        let vars = Self::nested_vars(&stmt);
+        self.vars.extend(vars);
+        stmt
     }
 
-    fn block_declaration(&mut self, vars: &mut Vec<TokenType>, is_loop: i32) -> Stmt {
-        if self.check(&TokenType::Break) {
-            if is_loop == -1 {
-                self.error("cannot have a break statement outside of a loop");
+    fn block_declaration(&mut self, vars: &mut Vec<Type>) -> Stmt {
+        if self.match_tokens(&[TokenType::Break, TokenType::Continue]) {
+            let keyword = self.previous().clone();
+            let name = if keyword.tt == TokenType::Break {"break"} else {"continue"};
+            if self.loops.is_empty() && (keyword.tt == TokenType::Continue || self.switches == 0) {
+                self.error(format!("cannot have a {} statement outside of a loop", name).as_str());
             }
-            self.advance();
-            self.consume(TokenType::Semicolon, "expected \";\" after break statement");
-            return Stmt::Break(is_loop);
+            //break label; and continue label; target an enclosing labeled loop
+            let label = if self.match_tokens(&[TokenType::Identifier]) {
+                let label = self.previous().literal.clone().unwrap().as_str();
+                if !self.loops.contains(&Some(label.clone())) {
+                    self.error(format!("undefined loop label \"{}\"", label).as_str());
+                }
+                Some(label)
+            } else {
+                None
+            };
+            self.consume(TokenType::Semicolon, format!("expected \";\" after {} statement", name).as_str());
+            return if keyword.tt == TokenType::Break {
+                Stmt::Break {label, line: keyword.line}
+            } else {
+                Stmt::Continue {label, line: keyword.line}
+            };
+        }
+        if self.check(&TokenType::Struct) || self.check(&TokenType::Enum) {
+            self.error("structs and enums must be declared at the top level");
         }
-        if self.match_tokens(&[TokenType::Bool, TokenType::Int, TokenType::Float, TokenType::String]) {
-            let t = self.previous().clone();
+        if self.check(&TokenType::Global) || self.check(&TokenType::Export) {
+            self.error("globals must be declared at the top level");
+        }
+        if self.check(&TokenType::Const) {
+            self.error("constants must be declared at the top level");
+        }
+        if self.check(&TokenType::Extern) {
+            self.error("extern functions must be declared at the top level");
+        }
+        if self.match_type() {
+            let (t, size) = self.type_decl();
             self.consume(TokenType::Identifier, "expected identifier after type declaration");
             let name = self.previous().clone();
             if self.match_tokens(&[TokenType::LeftParen]) {
                 self.error("cannot have a function declaration inside a block");
                 std::process::exit(0);
+            } else if self.check(&TokenType::Comma) {
+                return self.destructure(name, t, size); //its variables are added by nested_vars
             } else {
-                vars.push(t.tt);
-                return self.var_decl(name, t)
+                vars.push(t.clone());
+                return self.var_decl(name, t, size)
             }
         }
-        self.statement(is_loop)
+        self.statement()
     }
 
-    fn fn_decl(&mut self, name: Token, return_type: TokenType) -> Stmt {
-        let mut params: Vec<(TokenType, Token)> = Vec::new();
-        if self.match_tokens(&[TokenType::Bool, TokenType::Int, TokenType::Float, TokenType::String]) {
-            let t = self.previous().tt;
+    fn fn_decl(&mut self, name: Token, return_type: Type) -> Stmt {
+        let params = self.params();
+        let body: Box<Stmt> = Box::new(self.block(Some(return_type.clone())));
+        Stmt::Fn {
+            name: match name.literal {
+                Some(Value::String(s)) => s,
+                _ => {self.error("function name must be a string"); std::process::exit(0);}
+            },
+            params,
+            body,
+            return_type,
+            line: name.line,
+        }
+    }
+
+    fn extern_decl(&mut self) -> Stmt {
+        //extern "module" type name(params); imports module.name from js
+        let module = match self.consume(TokenType::Value, "expected a module name string after extern").literal.clone() {
+            Some(Value::String(s)) => s,
+            _ => {self.error("the module name of an extern function must be a string"); std::process::exit(0);},
+        };
+        if !self.match_tokens(&[TokenType::Void]) && !self.match_type() {
+            self.error("expected a return type after the module name");
+        }
+        let return_type = self.param_type();
+        let name = self.consume(TokenType::Identifier, "expected function name after return type").clone();
+        self.consume(TokenType::LeftParen, "expected \"(\" after function name");
+        let params = self.params();
+        self.consume(TokenType::Semicolon, "expected \";\" after extern declaration, extern functions have no body");
+        Stmt::Extern {
+            module,
+            name: name.literal.unwrap().as_str(),
+            params,
+            return_type,
+            line: name.line,
+        }
+    }
+
+    fn params(&mut self) -> Vec<(Type, Token)> {
+        //the parameters after "(", up to and including ")"
+        let mut params: Vec<(Type, Token)> = Vec::new();
+        if self.match_type() {
+            let t = self.param_type();
             let identifier = self.consume(TokenType::Identifier, "expected identifier after type declaration");
             params.push((t, identifier.clone()));
         }
         while !self.check(&TokenType::RightParen) {
             self.consume(TokenType::Comma, "expected \",\" after identifier");
-            if !self.match_tokens(&[TokenType::Bool, TokenType::Int, TokenType::Float, TokenType::String]) {
+            if !self.match_type() {
                 self.error("expected type declaration after comma");
             }
-            let t = self.previous().tt;
+            let t = self.param_type();
             let identifier = self.consume(TokenType::Identifier, "expected identifier after type declaration");
             params.push((t, identifier.clone()));
         }
         self.consume(TokenType::RightParen, "expected \")\" after function declaration");
-        let body: Box<Stmt>;
-        body = Box::new(self.block(Some(return_type), -1));
-        Stmt::Fn {
-            name: match name.literal {
-                Some(Value::String(s)) => s,
-                _ => {self.error("function name must be a string"); std::process::exit(0);}
+        params
+    }
+
+    fn global_decl(&mut self, exported: bool) -> Stmt {
+        //global int x = 0; is visible in every function, export global int x = 0; is also visible to js
+        if !self.match_type() {
+            self.error("expected a type after global");
+        }
+        let (t, size) = self.type_decl();
+        let name = self.consume(TokenType::Identifier, "expected identifier after type declaration").clone();
+        match self.var_decl(name, t, size) {
+            Stmt::Var {name, value, t, line} => Stmt::Global {
+                name,
+                value,
+                t,
+                exported,
+                line,
             },
-            params,
-            body,
-            return_type,
+            _ => {self.error("expected a global declaration"); std::process::exit(0);},
+        }
+    }
+
+    fn const_decl(&mut self) -> Stmt {
+        //const int N = 1000; is replaced by its value wherever N is used
+        if !self.match_type() {
+            self.error("expected a type after const");
+        }
+        let t = self.param_type();
+        if !matches!(t, Type::Int | Type::Uint | Type::Long | Type::Float | Type::Bool | Type::String) {
+            self.error("a constant must be an int, uint, long, float, bool or string");
+        }
+        let name = self.consume(TokenType::Identifier, "expected identifier after type declaration").clone();
+        self.consume(TokenType::Equal, "expected \"=\" after constant name, constants need a value");
+        let value = self.expression();
+        self.consume(TokenType::Semicolon, "expected \";\" after constant declaration");
+        let line = name.line;
+        Stmt::Const {name, value, t, line}
+    }
+
+    fn struct_decl(&mut self) -> Stmt {
+        //struct Name { type field; ... }
+        let name = self.consume(TokenType::Identifier, "expected struct name after struct").clone();
+        let name_str = name.literal.clone().unwrap().as_str();
+        if self.structs.contains(&name_str) || self.enums.contains(&name_str) {
+            self.error(format!("type \"{}\" already declared", name_str).as_str());
+        }
+        self.structs.push(name_str.clone()); //pushed before the fields, so a struct can reference itself
+        self.consume(TokenType::LeftBrace, "expected \"{\" after struct name");
+        let mut fields: Vec<(Type, Token)> = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            if !self.match_type() {
+                self.error("expected a field type");
+            }
+            let t = self.param_type();
+            let field = self.consume(TokenType::Identifier, "expected field name after type").clone();
+            if fields.iter().any(|f| f.1.literal == field.literal) {
+                self.error(format!("field \"{}\" already declared", field.literal.clone().unwrap().as_str()).as_str());
+            }
+            self.consume(TokenType::Semicolon, "expected \";\" after field");
+            fields.push((t, field));
+        }
+        self.consume(TokenType::RightBrace, "expected \"}\" after struct fields");
+        if fields.is_empty() {
+            self.error("a struct must have at least one field");
+        }
+        Stmt::Struct {
+            name: name_str,
+            fields,
+            line: name.line,
+        }
+    }
+
+    fn enum_decl(&mut self) -> Stmt {
+        //enum Name { Variant, ... }
+        let name = self.consume(TokenType::Identifier, "expected enum name after enum").clone();
+        let name_str = name.literal.clone().unwrap().as_str();
+        if self.structs.contains(&name_str) || self.enums.contains(&name_str) {
+            self.error(format!("type \"{}\" already declared", name_str).as_str());
+        }
+        self.enums.push(name_str.clone());
+        self.consume(TokenType::LeftBrace, "expected \"{\" after enum name");
+        let mut variants: Vec<Token> = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let variant = self.consume(TokenType::Identifier, "expected variant name").clone();
+            if variants.iter().any(|v| v.literal == variant.literal) {
+                self.error(format!("variant \"{}\" already declared", variant.literal.clone().unwrap().as_str()).as_str());
+            }
+            variants.push(variant);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "expected \"}\" after enum variants");
+        if variants.is_empty() {
+            self.error("an enum must have at least one variant");
+        }
+        Stmt::Enum {
+            name: name_str,
+            variants,
             line: name.line,
         }
     }
 
-    fn var_decl(&mut self, name: Token, t: Token) -> Stmt {
+    fn match_type(&mut self) -> bool {
+        //a type keyword or the name of a struct or enum, Point(...) is a constructor call and not a type
+        if self.match_tokens(&[TokenType::Bool, TokenType::Int, TokenType::Uint, TokenType::Long, TokenType::Float, TokenType::String, TokenType::List, TokenType::Fn]) {
+            return true;
+        }
+        if self.check(&TokenType::Identifier) && self.peek_next().tt != TokenType::LeftParen {
+            let name = self.peek().literal.clone().unwrap().as_str();
+            if self.structs.contains(&name) || self.enums.contains(&name) {
+                self.advance();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn type_decl(&mut self) -> (Type, Option<Expr>) {
+        //the base type was just matched, an array can have a size: int[10] xs;
+        let t = self.base_type();
+        if self.match_tokens(&[TokenType::LeftSquare]) {
+            if t == Type::Void {
+                self.error("cannot have an array of type void");
+            }
+            if let Type::List(_) = t {
+                self.error("cannot have an array of lists");
+            }
+            if self.match_tokens(&[TokenType::RightSquare]) {
+                return (Type::Array(Box::new(t)), None);
+            }
+            let size = self.expression();
+            self.consume(TokenType::RightSquare, "expected \"]\" after array size");
+            return (Type::Array(Box::new(t)), Some(size));
+        }
+        (t, None)
+    }
+
+    fn param_type(&mut self) -> Type {
+        let (t, size) = self.type_decl();
+        if size.is_some() {
+            self.error("a parameter cannot have an array size, use type[]");
+        }
+        t
+    }
+
+    fn base_type(&mut self) -> Type {
+        match self.previous().tt {
+            TokenType::Int => Type::Int,
+            TokenType::Uint => Type::Uint,
+            TokenType::Long => Type::Long,
+            TokenType::Float => Type::Float,
+            TokenType::Bool => Type::Bool,
+            TokenType::String => Type::String,
+            TokenType::Void => Type::Void,
+            TokenType::Fn => {
+                //fn(int, float) -> int, without "->" the function returns void
+                self.consume(TokenType::LeftParen, "expected \"(\" after fn");
+                let mut params = vec![];
+                while !self.check(&TokenType::RightParen) {
+                    if !params.is_empty() {
+                        self.consume(TokenType::Comma, "expected \",\" between parameter types");
+                    }
+                    if !self.match_type() {
+                        self.error("expected a parameter type");
+                    }
+                    params.push(self.param_type());
+                }
+                self.consume(TokenType::RightParen, "expected \")\" after parameter types");
+                let return_type = if self.match_tokens(&[TokenType::ThinArrow]) {
+                    if self.check(&TokenType::LeftParen) {
+                        self.tuple_type()
+                    } else {
+                        if !self.match_tokens(&[TokenType::Void]) && !self.match_type() {
+                            self.error("expected a return type after \"->\"");
+                        }
+                        self.param_type()
+                    }
+                } else {
+                    Type::Void
+                };
+                Type::Func(params, Box::new(return_type))
+            }
+            TokenType::Identifier => {
+                let name = self.previous().literal.clone().unwrap().as_str();
+                if self.enums.contains(&name) {
+                    Type::Enum(name)
+                } else {
+                    Type::Struct(name)
+                }
+            }
+            TokenType::List => {
+                //list<element>
+                self.consume(TokenType::Less, "expected \"<\" after list");
+                if !self.match_type() || self.previous().tt == TokenType::List {
+                    self.error("a list can only hold int, float, bool, string or structs");
+                }
+                let element = self.base_type();
+                self.consume(TokenType::Greater, "expected \">\" after list type");
+                Type::List(Box::new(element))
+            }
+            _ => {self.error("expected a type"); std::process::exit(0);}
+        }
+    }
+
+    fn tuple_type(&mut self) -> Type {
+        //(int, float), the return type of a function returning several values
+        self.consume(TokenType::LeftParen, "expected \"(\" before the types of a tuple");
+        let mut types = vec![];
+        while !self.check(&TokenType::RightParen) {
+            if !types.is_empty() {
+                self.consume(TokenType::Comma, "expected \",\" between the types of a tuple");
+            }
+            if !self.match_type() {
+                self.error("expected a type in the tuple");
+            }
+            types.push(self.param_type());
+        }
+        self.consume(TokenType::RightParen, "expected \")\" after the types of a tuple");
+        if types.len() < 2 {
+            self.error("a tuple must have at least two types");
+        }
+        Type::Tuple(types)
+    }
+
+    fn var_decl(&mut self, name: Token, t: Type, size: Option<Expr>) -> Stmt {
+        let line = name.line;
         let value = if self.match_tokens(&[TokenType::Equal]) {
-            Some(self.logical())
+            if size.is_some() {
+                self.error("an array with a size cannot have an initial value");
+            }
+            Some(self.expression())
+        } else if let (Some(size), Type::Array(element)) = (size, &t) {
+            Some(Expr::NewArray {
+                t: *element.clone(),
+                size: Box::new(size),
+                line,
+            })
         } else {
             None
         };
         self.consume(TokenType::Semicolon, "expected \";\" after variable declaration");
-        let line = name.line;
         Stmt::Var {
             name,
             value,
-            t: t.tt,
+            t,
             line,
         }
     }
 
-    fn statement(&mut self, is_loop: i32) -> Stmt {
-        if self.match_tokens(&[TokenType::Print]) {
-            if is_loop >= 0 {
-                self.error("print inside loops is a feature that is not yet implemented, sorry :(");
+    fn destructure(&mut self, name: Token, t: Type, size: Option<Expr>) -> Stmt {
+        //int q, int r = divmod(a, b); declares a variable for every value of the tuple
+        let line = name.line;
+        let mut targets = vec![(t, name)];
+        while self.match_tokens(&[TokenType::Comma]) {
+            if !self.match_type() {
+                self.error("expected the type of the next variable");
             }
+            let t = self.param_type();
+            self.consume(TokenType::Identifier, "expected identifier after type declaration");
+            targets.push((t, self.previous().clone()));
+        }
+        if size.is_some() {
+            self.error("cannot give an array a size when declaring several variables, use type[]");
+        }
+        self.consume(TokenType::Equal, "expected \"=\" and a value after the variables");
+        let value = self.expression();
+        self.consume(TokenType::Semicolon, "expected \";\" after variable declaration");
+        Stmt::Destructure {
+            targets,
+            value,
+            line,
+        }
+    }
+
+    fn statement(&mut self) -> Stmt {
+        if self.check(&TokenType::Identifier) && self.peek_next().tt == TokenType::Colon {
+            //label: while ... or label: for ...
+            let label = self.advance().literal.clone().unwrap().as_str();
+            self.advance();
+            if self.loops.contains(&Some(label.clone())) {
+                self.error(format!("loop label \"{}\" is already used by an enclosing loop", label).as_str());
+            }
+            if self.match_tokens(&[TokenType::While]) {
+                return self.while_stmt(Some(label));
+            }
+            if self.match_tokens(&[TokenType::For]) {
+                return self.for_stmt(Some(label));
+            }
+            self.error("a label must be followed by a loop");
+        }
+        if self.match_tokens(&[TokenType::Print]) {
             return self.print_stmt();
         }
         if self.match_tokens(&[TokenType::Return]) {
             return self.return_stmt();
         }
         if self.match_tokens(&[TokenType::If]) {
-            return self.if_stmt(is_loop);
+            return self.if_stmt();
         }
         if self.match_tokens(&[TokenType::While]) {
-            return self.while_stmt();
+            return self.while_stmt(None);
+        }
+        if self.match_tokens(&[TokenType::For]) {
+            return self.for_stmt(None);
+        }
+        if self.match_tokens(&[TokenType::Match]) {
+            return self.match_stmt();
+        }
+        if self.match_tokens(&[TokenType::Switch]) {
+            return self.switch_stmt();
+        }
+        if self.check(&TokenType::LeftBrace) {
+            return self.block(None);
         }
         self.expr_stmt()
     }
 
     fn print_stmt(&mut self) -> Stmt {
-        let expr = self.parse_print(); // only can have binary(+) with primary
+        let expr = self.parse_print(); // only can have binary(+) with unary operations, calls and primaries
         self.consume(TokenType::Semicolon, "expected \";\" after print statement");
         Stmt::Print {
             expr,
@@ -160,7 +549,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_print(&mut self) -> Expr {
-        let mut left: Expr = self.primary();
+        let mut left: Expr = self.unary();
         while self.match_tokens(&[TokenType::Plus]) {
             let operator = self.previous().clone();
             let right: Expr = self.parse_print();
@@ -179,16 +568,20 @@ impl<'a> Parser<'a> {
         if self.match_tokens(&[TokenType::Semicolon]) {
             self.error("expected expression after return statement");
         }
-        let value = self.logical();
+        let value = self.expression();
         self.consume(TokenType::Semicolon, "expected \";\" after return statement");
-        Stmt::Return{returnee: value, return_type: self.return_type, line: self.previous().line}
+        Stmt::Return{returnee: value, return_type: self.return_type.clone(), line: self.previous().line}
     }
 
-    fn if_stmt(&mut self, is_loop: i32) -> Stmt {
-        let condition = self.logical();
-        let then_branch = Box::new(self.block(None, if is_loop != -1 {is_loop + 1} else {-1}));
+    fn if_stmt(&mut self) -> Stmt {
+        let condition = self.expression();
+        let then_branch = Box::new(self.block(None));
         let else_branch = if self.match_tokens(&[TokenType::Else]) {
-            Some(Box::new(self.block(None, is_loop)))
+            if self.match_tokens(&[TokenType::If]) {
+                Some(Box::new(self.if_stmt())) //else if
+            } else {
+                Some(Box::new(self.block(None)))
+            }
         } else {
             None
         };
@@ -200,80 +593,209 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn while_stmt(&mut self) -> Stmt {
-        let condition = self.logical();
-        let block = Box::new(self.block(None, 0));
+    fn while_stmt(&mut self, label: Option<String>) -> Stmt {
+        let condition = self.expression();
+        self.loops.push(label.clone());
+        let block = Box::new(self.block(None));
+        self.loops.pop();
         Stmt::While {
             condition,
             block,
+            label,
             line: self.previous().line,
         }
     }
 
-    fn block(&mut self, enforce_return_type: Option<TokenType>, is_loop: i32) -> Stmt {
-        self.consume(TokenType::LeftBrace, "block must start with a \"{\"");
-        let mut had_return: bool = false;
-        if enforce_return_type.is_some() {
-            self.return_type = enforce_return_type.unwrap();
+    fn for_stmt(&mut self, label: Option<String>) -> Stmt {
+        //for (init; condition; increment) { code }, every part can be empty
+        let line = self.previous().line;
+        self.consume(TokenType::LeftParen, "expected \"(\" after for");
+        let init = if self.match_tokens(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_type() {
+            let (t, size) = self.type_decl();
+            self.consume(TokenType::Identifier, "expected identifier after type declaration");
+            let name = self.previous().clone();
+            Some(Box::new(self.var_decl(name, t, size)))
+        } else {
+            let expr = self.assignment();
+            self.consume(TokenType::Semicolon, "expected \";\" after for loop initializer");
+            Some(Box::new(Stmt::Expression(expr)))
+        };
+        let condition = if self.check(&TokenType::Semicolon) {
+            Expr::Literal{val: Value::Bool(true)}
+        } else {
+            self.expression()
+        };
+        self.consume(TokenType::Semicolon, "expected \";\" after for loop condition");
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.assignment())
+        };
+        self.consume(TokenType::RightParen, "expected \")\" after for loop increment");
+        self.loops.push(label.clone());
+        let block = Box::new(self.block(None));
+        self.loops.pop();
+        Stmt::For {
+            init,
+            condition,
+            increment,
+            block,
+            label,
+            line,
+        }
+    }
+
+    fn match_stmt(&mut self) -> Stmt {
+        //match value { Variant => { code }, _ => { code } }
+        let line = self.previous().line;
+        let value = self.expression();
+        self.consume(TokenType::LeftBrace, "expected \"{\" after match value");
+        let mut arms: Vec<(Option<Token>, Stmt)> = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let pattern = self.consume(TokenType::Identifier, "expected a variant or _ in match arm").clone();
+            let pattern = if pattern.literal == Some(Value::String("_".to_string())) {
+                None
+            } else {
+                Some(pattern)
+            };
+            self.consume(TokenType::Arrow, "expected \"=>\" after match pattern");
+            let block = self.block(None);
+            arms.push((pattern, block));
+            if !self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::RightBrace) {
+                self.error("expected \",\" after match arm");
+            }
         }
-        let mut vars1: Vec<TokenType> = Vec::new();
+        self.consume(TokenType::RightBrace, "expected \"}\" after match arms");
+        Stmt::Match {
+            value,
+            arms,
+            line,
+        }
+    }
+
+    fn switch_stmt(&mut self) -> Stmt {
+        //switch value { case 1, 2: code case 3: code default: code }, cases do not fall through
+        let line = self.previous().line;
+        let value = self.expression();
+        self.consume(TokenType::LeftBrace, "expected \"{\" after switch value");
+        self.switches += 1;
+        let mut cases: Vec<(Vec<Expr>, Stmt)> = Vec::new();
+        let mut default: Option<Box<Stmt>> = None;
+        while !self.check(&TokenType::RightBrace) {
+            if default.is_some() {
+                self.error("the default case must be the last case");
+            }
+            if self.match_tokens(&[TokenType::Default]) {
+                self.consume(TokenType::Colon, "expected \":\" after default");
+                default = Some(Box::new(self.case_body()));
+            } else {
+                self.consume(TokenType::Case, "expected case or default in switch");
+                let mut values = vec![self.expression()];
+                while self.match_tokens(&[TokenType::Comma]) {
+                    values.push(self.expression());
+                }
+                self.consume(TokenType::Colon, "expected \":\" after case values");
+                cases.push((values, self.case_body()));
+            }
+        }
+        self.switches -= 1;
+        self.consume(TokenType::RightBrace, "expected \"}\" after switch cases");
+        Stmt::Switch {
+            value,
+            cases,
+            default,
+            line,
+        }
+    }
+
+    fn case_body(&mut self) -> Stmt {
+        //the statements up to the next case
+        let mut vars1: Vec<Type> = Vec::new();
         let mut stmts: Vec<Stmt> = Vec::new();
-        while !self.is_at_end() && !self.check(&TokenType::RightBrace) {
-            let stmt = self.block_declaration(&mut vars1, is_loop);
-            match stmt.clone() {
-                Stmt::If {then_branch, else_branch, ..} => {
-                    match *then_branch.clone() {
-                        Stmt::Block {vars, ..} => {
-                            for var in vars {
-                                vars1.push(var);
-                            }
-                        }
-                        _ => {}
-                    }
-                    if let Some(else_branch) = else_branch {
-                        match *else_branch {
-                            Stmt::Block {vars, ..} => {
-                                for var in vars {
-                                    vars1.push(var);
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
+        while !self.is_at_end() && !self.check(&TokenType::Case) && !self.check(&TokenType::Default) && !self.check(&TokenType::RightBrace) {
+            let stmt = self.block_declaration(&mut vars1);
+            vars1.extend(Self::nested_vars(&stmt));
+            stmts.push(stmt);
+        }
+        Stmt::Block{stmts, vars: vars1}
+    }
+
+    fn nested_vars(stmt: &Stmt) -> Vec<Type> {
+        //variables declared in the blocks of a statement, they are locals of the enclosing function
+        let mut vars1: Vec<Type> = Vec::new();
+        match stmt {
+            Stmt::If {then_branch, else_branch, ..} => {
+                vars1.extend(Self::nested_vars(then_branch));
+                if let Some(else_branch) = else_branch {
+                    vars1.extend(Self::nested_vars(else_branch)); //a block or an else if
                 }
-                Stmt::While {block, ..} => {
-                    match *block {
-                        Stmt::Block {vars, ..} => {
-                            for var in vars {
-                                vars1.push(var);
-                            }
-                        }
-                        _ => {}
-                    }
+            }
+            Stmt::While {block, ..} => {
+                if let Stmt::Block {vars, ..} = &**block {
+                    vars1.extend(vars.iter().cloned());
                 }
-                Stmt::Block {vars, ..} => {
-                    for var in vars {
-                        vars1.push(var);
+            }
+            Stmt::For {init, block, ..} => {
+                if let Some(init) = init {
+                    if let Stmt::Var {t, ..} = &**init {
+                        vars1.push(t.clone());
                     }
                 }
-                _ => {}
+                if let Stmt::Block {vars, ..} = &**block {
+                    vars1.extend(vars.iter().cloned());
+                }
+            }
+            Stmt::Block {vars, ..} => {
+                vars1.extend(vars.iter().cloned());
+            }
+            Stmt::Destructure {targets, ..} => {
+                vars1.extend(targets.iter().map(|(t, _)| t.clone()));
             }
+            Stmt::Match {arms, ..} => {
+                for (_, block) in arms {
+                    vars1.extend(Self::nested_vars(block));
+                }
+            }
+            Stmt::Switch {cases, default, ..} => {
+                for (_, block) in cases {
+                    vars1.extend(Self::nested_vars(block));
+                }
+                if let Some(default) = default {
+                    vars1.extend(Self::nested_vars(default));
+                }
+            }
+            _ => {}
+        }
+        vars1
+    }
+
+    fn block(&mut self, enforce_return_type: Option<Type>) -> Stmt {
+        self.consume(TokenType::LeftBrace, "block must start with a \"{\"");
+        let mut had_return: bool = false;
+        if let Some(return_type) = enforce_return_type.clone() {
+            self.return_type = return_type;
+        }
+        let mut vars1: Vec<Type> = Vec::new();
+        let mut stmts: Vec<Stmt> = Vec::new();
+        while !self.is_at_end() && !self.check(&TokenType::RightBrace) {
+            let stmt = self.block_declaration(&mut vars1);
+            vars1.extend(Self::nested_vars(&stmt));
             stmts.push(stmt);
             if let Stmt::Return {..} = stmts[stmts.len() - 1] {
                 had_return = true;
             }
         }
-        if let Some(TokenType::Void) = enforce_return_type {
+        if let Some(Type::Void) = enforce_return_type {
             if had_return {
                 self.error("void functions cannot return a value");
             }
-        } else if !enforce_return_type.is_none() {
-            if !had_return {
-                self.error("non void functions must return a value");
-            }
+        } else if enforce_return_type.is_some() && !had_return {
+            self.error("non void functions must return a value");
         }
         if enforce_return_type.is_some() {
-            self.return_type = TokenType::Void;
+            self.return_type = Type::Void;
         }
         self.consume(TokenType::RightBrace, "block must end with a \"}\"");
         Stmt::Block{stmts, vars: vars1}
@@ -290,44 +812,65 @@ impl<'a> Parser<'a> {
     }
 
     fn assignment(&mut self) -> Expr {
-        let identifier = self.logical();
-        if self.match_tokens(&[TokenType::Equal, TokenType::PlusEqual, TokenType::MinusEqual, TokenType::StarEqual, TokenType::SlashEqual]) {
+        let identifier = self.expression();
+        if self.match_tokens(&[TokenType::Equal, TokenType::PlusEqual, TokenType::MinusEqual, TokenType::StarEqual, TokenType::SlashEqual,
+            TokenType::BitAndEqual, TokenType::BitOrEqual, TokenType::BitXorEqual,
+            TokenType::ShiftLeftEqual, TokenType::ShiftRightEqual, TokenType::ShiftRightUnsignedEqual]) {
+            let operator = self.previous().clone();
+            let mut value = self.expression();
+            let mut binary = None;
+            if operator.tt != TokenType::Equal {
+                binary = Some(Token {
+                    tt: match operator.tt {
+                        TokenType::PlusEqual => TokenType::Plus,
+                        TokenType::MinusEqual => TokenType::Minus,
+                        TokenType::StarEqual => TokenType::Star,
+                        TokenType::SlashEqual => TokenType::Slash,
+                        TokenType::BitAndEqual => TokenType::BitAnd,
+                        TokenType::BitOrEqual => TokenType::BitOr,
+                        TokenType::BitXorEqual => TokenType::BitXor,
+                        TokenType::ShiftLeftEqual => TokenType::ShiftLeft,
+                        TokenType::ShiftRightEqual => TokenType::ShiftRight,
+                        TokenType::ShiftRightUnsignedEqual => TokenType::ShiftRightUnsigned,
+                        _ => {self.error("incorrect token at assignment"); std::process::exit(0);},
+                    },
+                    literal: None,
+                    line: operator.line,
+                });
+            }
             match identifier {
                 Expr::Variable{name, line} => {
-                    match self.previous().tt {
-                        TokenType::Equal => {
-                            return Expr::Assign {
-                                name,
-                                value: Box::new(self.logical()),
-                                line,
-                            }
-                        }
-                        TokenType::PlusEqual | TokenType::MinusEqual | TokenType::StarEqual | TokenType::SlashEqual => {
-                            return Expr::Assign {
-                                name: name.clone(),
-                                value: Box::new(Expr::Binary {
-                                    left: Box::new(Expr::Variable{name, line}),
-                                    operator: Token {
-                                        tt: match self.previous().tt {
-                                            TokenType::PlusEqual => TokenType::Plus,
-                                            TokenType::MinusEqual => TokenType::Minus,
-                                            TokenType::StarEqual => TokenType::Star,
-                                            TokenType::SlashEqual => TokenType::Slash,
-                                            _ => {self.error("incorrect token at assignment"); std::process::exit(0);},
-                                        },
-                                        literal: None,
-                                        line,
-                                    },
-                                    right: Box::new(self.logical()),
-                                    line,
-                                }),
-                                line,
-                            }
-                        }
-                        _ => {
-                            self.error("after a =, must have an assignment");
-                            std::process::exit(0);
-                        }
+                    //reading a variable has no side effects, so x += 1 becomes x = x + 1
+                    if let Some(operator) = binary {
+                        value = Expr::Binary {
+                            left: Box::new(Expr::Variable{name: name.clone(), line}),
+                            operator,
+                            right: Box::new(value),
+                            line,
+                        };
+                    }
+                    return Expr::Assign {
+                        name,
+                        value: Box::new(value),
+                        line,
+                    }
+                }
+                Expr::Index{array, index, line} => {
+                    return Expr::SetIndex {
+                        array,
+                        index,
+                        value: Box::new(value),
+                        operator: binary,
+                        line,
+                    }
+                }
+                Expr::Get{object, name, line} => {
+                    return Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
+                        operator: binary,
+                        line,
                     }
                 }
                 _ => {
@@ -339,93 +882,75 @@ impl<'a> Parser<'a> {
         identifier
     }
 
-    fn logical(&mut self) -> Expr {
-        let mut left: Expr = self.equality();
-        while self.match_tokens(&[TokenType::And, TokenType::Or]) {
-            let operator = self.previous().clone();
-            let right: Expr = self.equality();
-            let line = operator.line;
-            left = Expr::Binary {
-                left: Box::new(left),
-                operator,
-                right: Box::new(right),
-                line,
-            };
-        }
-        left
-    }
-
-    fn equality(&mut self) -> Expr {
-        let left: Expr = self.comparison();
-        if self.match_tokens(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.previous().clone();
-            let right: Expr = self.comparison();
-            let line = operator.line;
-            return Expr::Binary {
-                left: Box::new(left),
-                operator,
-                right: Box::new(right),
+    fn expression(&mut self) -> Expr {
+        //condition ? a : b binds looser than every binary operator and is right associative
+        let condition = self.binary(1);
+        if self.match_tokens(&[TokenType::Question]) {
+            let line = self.previous().line;
+            let then_branch = self.expression();
+            self.consume(TokenType::Colon, "expected \":\" in conditional expression");
+            let else_branch = self.expression();
+            return Expr::Ternary {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
                 line,
             };
         }
-        left
+        condition
     }
 
-    fn comparison(&mut self) -> Expr {
-        let left: Expr = self.term();
-        if self.match_tokens(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let operator = self.previous().clone();
-            let right: Expr = self.term();
-            let line = operator.line;
-            return Expr::Binary {
-                left: Box::new(left),
-                operator,
-                right: Box::new(right),
-                line,
-            };
+    //binary operators, from the loosest to the tightest binding:
+    // 1  ||
+    // 2  &&
+    // 3  == !=
+    // 4  < <= > >=
+    // 5  |
+    // 6  ^
+    // 7  &
+    // 8  << >> >>>
+    // 9  + -
+    //10  * / %
+    //every level is left associative (10 - 3 - 2 is (10 - 3) - 2),
+    //unary operators (! - ~) bind tighter than all of them, and calls, indexing and field access tighter still
+    fn precedence(tt: TokenType) -> Option<u8> {
+        match tt {
+            TokenType::Or => Some(1),
+            TokenType::And => Some(2),
+            TokenType::EqualEqual | TokenType::BangEqual => Some(3),
+            TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual => Some(4),
+            TokenType::BitOr => Some(5),
+            TokenType::BitXor => Some(6),
+            TokenType::BitAnd => Some(7),
+            TokenType::ShiftLeft | TokenType::ShiftRight | TokenType::ShiftRightUnsigned => Some(8),
+            TokenType::Plus | TokenType::Minus => Some(9),
+            TokenType::Star | TokenType::Slash | TokenType::Modulo => Some(10),
+            _ => None,
         }
-        left
     }
 
-    fn term(&mut self) -> Expr {
-        let left: Expr = self.factor();
-        while self.match_tokens(&[TokenType::Plus, TokenType::Minus]) {
-            let operator = self.previous().clone();
-            let right: Expr = self.term();
-            let line = operator.line;
-            return Expr::Binary {
-                left: Box::new(left),
-                operator,
-                right: Box::new(right),
-                line,
-            };
-        }
-        left
-    }
-
-    fn factor(&mut self) -> Expr {
+    fn binary(&mut self, min_precedence: u8) -> Expr {
+        //precedence climbing: parses operators that bind at least as tight as min_precedence
         let mut left: Expr = self.unary();
-        while self.match_tokens(&[TokenType::Slash, TokenType::Star, TokenType::Modulo]) {
-            let operator = self.previous().clone();
-            let right: Expr = self.unary();
+        while let Some(precedence) = Self::precedence(self.peek().tt) {
+            if precedence < min_precedence {
+                break;
+            }
+            let operator = self.advance().clone();
+            let right: Expr = self.binary(precedence + 1); //the right side only takes tighter operators, so the level is left associative
             let line = operator.line;
             left = Expr::Binary {
                 left: Box::new(left),
                 operator,
                 right: Box::new(right),
-                line
+                line,
             };
         }
         left
     }
 
     fn unary(&mut self) -> Expr {
-        if self.match_tokens(&[TokenType::Bang, TokenType::Minus]) {
+        if self.match_tokens(&[TokenType::Bang, TokenType::Minus, TokenType::BitNot]) {
             let operator = self.previous().clone();
             let e = self.unary();
             let line = operator.line;
@@ -439,6 +964,33 @@ impl<'a> Parser<'a> {
     }
 
     fn call(&mut self) -> Expr {
+        let mut expr = self.finish_call();
+        loop {
+            if self.match_tokens(&[TokenType::LeftSquare]) {
+                let line = self.previous().line;
+                let index = self.expression();
+                self.consume(TokenType::RightSquare, "expected \"]\" after index");
+                expr = Expr::Index {
+                    array: Box::new(expr),
+                    index: Box::new(index),
+                    line,
+                };
+            } else if self.match_tokens(&[TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, "expected field name after \".\"").clone();
+                let line = name.line;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                    line,
+                };
+            } else {
+                break;
+            }
+        }
+        expr
+    }
+
+    fn finish_call(&mut self) -> Expr {
         let expr = self.primary();
         let line = self.previous().line;
         if self.match_tokens(&[TokenType::LeftParen]) {
@@ -453,9 +1005,9 @@ impl<'a> Parser<'a> {
                 };
             }
             let mut vec: Vec<Expr> = Vec::new();
-            vec.push(self.logical());
+            vec.push(self.expression());
             while self.match_tokens(&[TokenType::Comma]) {
-                vec.push(self.logical());
+                vec.push(self.expression());
             }
             self.consume(TokenType::RightParen, "call must end with a \")\"");
             return Expr::Call {
@@ -468,20 +1020,70 @@ impl<'a> Parser<'a> {
     }
 
     fn primary(&mut self) -> Expr {
+        if matches!(self.peek().tt, TokenType::Int | TokenType::Uint | TokenType::Long | TokenType::Float | TokenType::Bool)
+            && self.peek_next().tt == TokenType::LeftParen {
+            //casts look like calls to the type: float(i)
+            let line = self.advance().line;
+            let t = self.base_type();
+            self.advance();
+            let value = self.expression();
+            self.consume(TokenType::RightParen, "expected \")\" after cast");
+            return Expr::Cast {
+                t,
+                value: Box::new(value),
+                line,
+            };
+        }
         if self.match_tokens(&[TokenType::Value]) {
             match self.previous().clone().literal {
-                Some(Value::Bool(b)) => return Expr::Literal{val: Value::Bool(b), line: self.previous().line},
-                Some(Value::Int(n)) => return Expr::Literal{val: Value::Int(n), line: self.previous().line},
-                Some(Value::Float(n)) => return Expr::Literal{val: Value::Float(n), line: self.previous().line},
-                Some(Value::String(s)) => return Expr::Literal{val: Value::String(s), line: self.previous().line},
+                Some(Value::Bool(b)) => return Expr::Literal{val: Value::Bool(b)},
+                Some(Value::Int(n)) => return Expr::Literal{val: Value::Int(n)},
+                Some(Value::Uint(n)) => return Expr::Literal{val: Value::Uint(n)},
+                Some(Value::Long(n)) => return Expr::Literal{val: Value::Long(n)},
+                Some(Value::Float(n)) => return Expr::Literal{val: Value::Float(n)},
+                Some(Value::String(s)) => return Expr::Literal{val: Value::String(s)},
                 _ => {self.error("parser cannot process string reference"); std::process::exit(0);}
             }
         }
         if self.match_tokens(&[TokenType::Identifier]) {
-            return Expr::Variable{name: self.previous().clone(), line: self.previous().line};
+            let mut name = self.previous().clone();
+            //file.function(...) calls a function of an imported file
+            if self.check(&TokenType::Dot) && self.peek_next().tt == TokenType::Identifier
+                && self.tokens.get(self.current + 2).is_some_and(|t| t.tt == TokenType::LeftParen) {
+                self.advance();
+                let function = self.advance().literal.clone().unwrap().as_str();
+                name.literal = Some(Value::String(format!("{}.{}", name.literal.unwrap().as_str(), function)));
+            }
+            let line = name.line;
+            return Expr::Variable{name, line};
+        }
+        if self.check(&TokenType::LeftParen) && self.is_lambda() {
+            //(type name, ...) => expression
+            let line = self.advance().line;
+            let params = self.params();
+            self.consume(TokenType::Arrow, "expected \"=>\" after the parameters of a lambda");
+            let body = self.expression();
+            return Expr::Lambda {
+                params,
+                body: Box::new(body),
+                line,
+            };
         }
         if self.match_tokens(&[TokenType::LeftParen]) {
-            let expression = self.logical();
+            let line = self.previous().line;
+            let expression = self.expression();
+            if self.check(&TokenType::Comma) {
+                //(a, b) is a tuple
+                let mut values = vec![expression];
+                while self.match_tokens(&[TokenType::Comma]) {
+                    values.push(self.expression());
+                }
+                self.consume(TokenType::RightParen, "expected \")\" after the values of a tuple");
+                return Expr::Tuple {
+                    values,
+                    line,
+                };
+            }
             self.consume(
                 TokenType::RightParen,
                 "expected \")\" after expression in grouping",
@@ -491,9 +1093,43 @@ impl<'a> Parser<'a> {
         self.error("primary must be a value, grouping or an identifier");
         std::process::exit(0);
     }
+    fn is_lambda(&self) -> bool {
+        //after "(": ") =>", a parameter type that is not a cast, or a struct or enum type followed by a name
+        let token = |i: usize| self.tokens.get(self.current + i).map(|t| t.tt).unwrap_or(TokenType::Eof);
+        match token(1) {
+            TokenType::RightParen => token(2) == TokenType::Arrow,
+            TokenType::Fn | TokenType::List | TokenType::String => true,
+            TokenType::Int | TokenType::Uint | TokenType::Long | TokenType::Float | TokenType::Bool => token(2) != TokenType::LeftParen,
+            TokenType::Identifier => {
+                let name = self.tokens[self.current + 1].literal.clone().unwrap().as_str();
+                (self.structs.contains(&name) || self.enums.contains(&name)) && token(2) == TokenType::Identifier
+            }
+            _ => false,
+        }
+    }
+
+    fn is_tuple_type(&self) -> bool {
+        //after "(": a type, and the matching ")" is followed by a function name and "(": (int, int) divmod(...)
+        let token = |i: usize| self.tokens.get(self.current + i).map(|t| t.tt).unwrap_or(TokenType::Eof);
+        let mut depth = 0;
+        let mut i = 0;
+        loop {
+            match token(i) {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => depth -= 1,
+                TokenType::Eof | TokenType::Semicolon | TokenType::LeftBrace => return false,
+                _ => {},
+            }
+            i += 1;
+            if depth == 0 {
+                return token(i) == TokenType::Identifier && token(i + 1) == TokenType::LeftParen;
+            }
+        }
+    }
+
     fn match_tokens(&mut self, types: &[TokenType]) -> bool {
         for tt in types {
-            if self.check(&tt) {
+            if self.check(tt) {
                 self.advance();
                 return true;
             }
@@ -520,6 +1156,9 @@ impl<'a> Parser<'a> {
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
+    fn peek_next(&self) -> &Token {
+        &self.tokens[(self.current + 1).min(self.tokens.len() - 1)]
+    }
     fn previous(&self) -> &Token {
         &self.tokens[self.current - 1]
     }
@@ -0,0 +1,1064 @@
+use wasm_encoder::{BlockType, Function, Instruction, MemArg, ValType};
+use crate::compiler::Compiler;
+
+//runtime helpers, compiled into the module after the keyscript functions
+//only the helpers that are actually used get emitted
+//strings are an i32 pointer to [length: i32][utf8 bytes] in linear memory
+//arrays are an i32 pointer to [length: i32][padding][elements], elements are 8 byte aligned
+//lists are an i32 pointer to [length: i32][capacity: i32][pointer to elements], the elements move when the list grows
+//the heap is a bump allocator, its pointer lives in a global
+
+pub const HEAP: u32 = 0; //global index of the heap pointer
+const LIST_CAPACITY: i32 = 4; //capacity of a new list
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Helper {
+    Alloc,      //(size) -> pointer
+    Concat,     //(string, string) -> string
+    Print,      //(string)
+    Digits,     //(unsigned i64, width) -> string of exactly width digits, zero padded
+    IntToStr,   //(i64) -> string, ints are extended before the call
+    FloatToStr, //(f64) -> string, rounded to 6 decimals without trailing zeros
+    BigFloatToStr, //(f64) -> string of a positive whole float too large for an i64
+    NewArray,   //(length, element size) -> array
+    ArrayIndex, //(array, index, element size) -> address of the element, traps when out of bounds
+    NewList,    //(element size) -> empty list
+    ListIndex,  //(list, index, element size) -> address of the element, traps when out of bounds
+    ListPush,   //(list, element size) -> address of the new last element, grows the list if it is full
+    ListPop,    //(list, element size) -> address of the removed last element, traps when empty
+    StrCompare, //(string, string) -> negative, 0 or positive, the bytes are compared in order
+    Substr,     //(string, start, length) -> string, start and length are clamped to the string
+    Find,       //(string, needle, from) -> index of the first needle at or after from, -1 if there is none
+    ToUpper,    //(string) -> string, only ascii letters change
+    ToLower,    //(string) -> string
+    Trim,       //(string) -> string without the spaces and control characters at both ends
+    Split,      //(string, separator) -> list of strings, an empty separator splits every byte
+}
+
+impl Helper {
+    pub fn params(&self) -> Vec<ValType> {
+        match self {
+            Helper::Alloc => vec![ValType::I32],
+            Helper::Concat => vec![ValType::I32, ValType::I32],
+            Helper::Print => vec![ValType::I32],
+            Helper::Digits => vec![ValType::I64, ValType::I32],
+            Helper::IntToStr => vec![ValType::I64],
+            Helper::FloatToStr | Helper::BigFloatToStr => vec![ValType::F64],
+            Helper::NewArray => vec![ValType::I32, ValType::I32],
+            Helper::ArrayIndex | Helper::ListIndex => vec![ValType::I32, ValType::I32, ValType::I32],
+            Helper::NewList => vec![ValType::I32],
+            Helper::ListPush | Helper::ListPop => vec![ValType::I32, ValType::I32],
+            Helper::StrCompare | Helper::Split => vec![ValType::I32, ValType::I32],
+            Helper::Substr | Helper::Find => vec![ValType::I32, ValType::I32, ValType::I32],
+            Helper::ToUpper | Helper::ToLower | Helper::Trim => vec![ValType::I32],
+        }
+    }
+
+    pub fn results(&self) -> Vec<ValType> {
+        match self {
+            Helper::Alloc => vec![ValType::I32],
+            Helper::Concat => vec![ValType::I32],
+            Helper::Print => vec![],
+            Helper::Digits | Helper::IntToStr | Helper::FloatToStr | Helper::BigFloatToStr => vec![ValType::I32],
+            Helper::NewArray | Helper::ArrayIndex => vec![ValType::I32],
+            Helper::NewList | Helper::ListIndex | Helper::ListPush | Helper::ListPop => vec![ValType::I32],
+            Helper::StrCompare | Helper::Substr | Helper::Find => vec![ValType::I32],
+            Helper::ToUpper | Helper::ToLower | Helper::Trim | Helper::Split => vec![ValType::I32],
+        }
+    }
+
+    pub fn compile(&self, comp: &mut Compiler) -> Function {
+        match self {
+            Helper::Alloc => {
+                //params: size, locals: pointer
+                let mut f = Function::new(vec![(1, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::GlobalGet(HEAP),
+                    Instruction::LocalSet(1),
+                    //bump the heap pointer, keeping it 8 byte aligned
+                    Instruction::GlobalGet(HEAP),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Add,
+                    Instruction::I32Const(7),
+                    Instruction::I32Add,
+                    Instruction::I32Const(-8),
+                    Instruction::I32And,
+                    Instruction::GlobalSet(HEAP),
+                    //grow the memory if the heap went past its end
+                    Instruction::GlobalGet(HEAP),
+                    Instruction::MemorySize(0),
+                    Instruction::I32Const(16),
+                    Instruction::I32Shl,
+                    Instruction::I32GtU,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::GlobalGet(HEAP),
+                    Instruction::MemorySize(0),
+                    Instruction::I32Const(16),
+                    Instruction::I32Shl,
+                    Instruction::I32Sub,
+                    Instruction::I32Const(16),
+                    Instruction::I32ShrU,
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::MemoryGrow(0),
+                    Instruction::I32Const(-1),
+                    Instruction::I32Eq,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::Unreachable, //out of memory
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(1),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::Concat => {
+                //params: a, b, locals: a length, b length, new string
+                let alloc = comp.helper(Helper::Alloc);
+                let mut f = Function::new(vec![(3, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalSet(2),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalSet(3),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Add,
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::Call(alloc),
+                    Instruction::LocalSet(4),
+                    Instruction::LocalGet(4),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Add,
+                    Instruction::I32Store(mem(2)),
+                    //copy a
+                    Instruction::LocalGet(4),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(2),
+                    Instruction::MemoryCopy {src_mem: 0, dst_mem: 0},
+                    //copy b after a
+                    Instruction::LocalGet(4),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(2),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(1),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(3),
+                    Instruction::MemoryCopy {src_mem: 0, dst_mem: 0},
+                    Instruction::LocalGet(4),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::Print => {
+                //console.log takes (offset, length)
+                let mut f = Function::new(vec![]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::Call(0),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::Digits => {
+                //params: value, width, locals: string, write position
+                let alloc = comp.helper(Helper::Alloc);
+                let mut f = Function::new(vec![(2, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(1),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::Call(alloc),
+                    Instruction::LocalSet(2),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Store(mem(2)),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(3),
+                    //write the digits backwards, from the last one
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(3),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::I32LeU,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Const(1),
+                    Instruction::I32Sub,
+                    Instruction::LocalTee(3),
+                    Instruction::LocalGet(0),
+                    Instruction::I64Const(10),
+                    Instruction::I64RemU,
+                    Instruction::I32WrapI64,
+                    Instruction::I32Const(48), //'0'
+                    Instruction::I32Add,
+                    Instruction::I32Store8(mem(0)),
+                    Instruction::LocalGet(0),
+                    Instruction::I64Const(10),
+                    Instruction::I64DivU,
+                    Instruction::LocalSet(0),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(2),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::IntToStr => {
+                //params: n, locals: absolute value, temp, digit count, digits
+                let digits = comp.helper(Helper::Digits);
+                let concat = comp.helper(Helper::Concat);
+                let minus = comp.make_string("-".to_string());
+                let mut f = Function::new(vec![(2, ValType::I64), (2, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I64Const(0),
+                    Instruction::I64LtS,
+                    Instruction::If(BlockType::Result(ValType::I64)),
+                    Instruction::I64Const(0),
+                    Instruction::LocalGet(0),
+                    Instruction::I64Sub,
+                    Instruction::Else,
+                    Instruction::LocalGet(0),
+                    Instruction::End,
+                    Instruction::LocalTee(1),
+                    Instruction::LocalSet(2),
+                    //count the digits, at least 1
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(3),
+                    Instruction::LocalGet(2),
+                    Instruction::I64Const(10),
+                    Instruction::I64DivU,
+                    Instruction::LocalTee(2),
+                    Instruction::I64Const(0),
+                    Instruction::I64Ne,
+                    Instruction::BrIf(0),
+                    Instruction::End,
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(3),
+                    Instruction::Call(digits),
+                    Instruction::LocalSet(4),
+                    Instruction::LocalGet(0),
+                    Instruction::I64Const(0),
+                    Instruction::I64LtS,
+                    Instruction::If(BlockType::Result(ValType::I32)),
+                    Instruction::I32Const(minus),
+                    Instruction::LocalGet(4),
+                    Instruction::Call(concat),
+                    Instruction::Else,
+                    Instruction::LocalGet(4),
+                    Instruction::End,
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::FloatToStr => {
+                //params: f, locals: integer part, fraction, fraction width, string, negative
+                let int_to_str = comp.helper(Helper::IntToStr);
+                let big_float_to_str = comp.helper(Helper::BigFloatToStr);
+                let digits = comp.helper(Helper::Digits);
+                let concat = comp.helper(Helper::Concat);
+                let nan = comp.make_string("NaN".to_string());
+                let inf = comp.make_string("inf".to_string());
+                let dot = comp.make_string(".".to_string());
+                let minus = comp.make_string("-".to_string());
+                let mut f = Function::new(vec![(2, ValType::I64), (3, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(0),
+                    Instruction::F64Ne,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::I32Const(nan),
+                    Instruction::Return,
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::F64Const(0.0),
+                    Instruction::F64Lt,
+                    Instruction::LocalSet(5),
+                    Instruction::LocalGet(0),
+                    Instruction::F64Abs,
+                    Instruction::LocalTee(0),
+                    Instruction::F64Const(f64::INFINITY),
+                    Instruction::F64Eq,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::I32Const(inf),
+                    Instruction::LocalSet(4),
+                    Instruction::Else,
+                    //the integer part would not fit in an i64, floats that large have no fraction
+                    Instruction::LocalGet(0),
+                    Instruction::F64Const(9223372036854775808.0),
+                    Instruction::F64Ge,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::LocalGet(0),
+                    Instruction::Call(big_float_to_str),
+                    Instruction::LocalSet(4),
+                    Instruction::Else,
+                    Instruction::LocalGet(0),
+                    Instruction::I64TruncSatF64S,
+                    Instruction::LocalSet(1),
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(0),
+                    Instruction::F64Trunc,
+                    Instruction::F64Sub,
+                    Instruction::F64Const(1000000.0),
+                    Instruction::F64Mul,
+                    Instruction::F64Nearest,
+                    Instruction::I64TruncSatF64S,
+                    Instruction::LocalTee(2),
+                    //the fraction rounded up to a whole number
+                    Instruction::I64Const(1000000),
+                    Instruction::I64Eq,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::LocalGet(1),
+                    Instruction::I64Const(1),
+                    Instruction::I64Add,
+                    Instruction::LocalSet(1),
+                    Instruction::I64Const(0),
+                    Instruction::LocalSet(2),
+                    Instruction::End,
+                    //a negative float that rounds to 0 prints without a sign
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I64Or,
+                    Instruction::I64Eqz,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::I32Const(0),
+                    Instruction::LocalSet(5),
+                    Instruction::End,
+                    Instruction::LocalGet(1),
+                    Instruction::Call(int_to_str),
+                    Instruction::LocalSet(4),
+                    Instruction::LocalGet(2),
+                    Instruction::I64Const(0),
+                    Instruction::I64Ne,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::I32Const(6),
+                    Instruction::LocalSet(3),
+                    //drop the trailing zeros
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(2),
+                    Instruction::I64Const(10),
+                    Instruction::I64RemU,
+                    Instruction::I64Const(0),
+                    Instruction::I64Ne,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I64Const(10),
+                    Instruction::I64DivU,
+                    Instruction::LocalSet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Const(1),
+                    Instruction::I32Sub,
+                    Instruction::LocalSet(3),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(4),
+                    Instruction::I32Const(dot),
+                    Instruction::Call(concat),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::Call(digits),
+                    Instruction::Call(concat),
+                    Instruction::LocalSet(4),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(5),
+                    Instruction::If(BlockType::Result(ValType::I32)),
+                    Instruction::I32Const(minus),
+                    Instruction::LocalGet(4),
+                    Instruction::Call(concat),
+                    Instruction::Else,
+                    Instruction::LocalGet(4),
+                    Instruction::End,
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::BigFloatToStr => {
+                //params: f, locals: digit count, index, quotient, power of 10
+                //like js only the first 17 digits are written, the rest are zeros
+                let int_to_str = comp.helper(Helper::IntToStr);
+                let digits = comp.helper(Helper::Digits);
+                let concat = comp.helper(Helper::Concat);
+                let mut f = Function::new(vec![(2, ValType::I32), (2, ValType::F64)]);
+                emit(&mut f, &[
+                    //count the digits
+                    Instruction::LocalGet(0),
+                    Instruction::LocalSet(3),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(1),
+                    Instruction::LocalGet(3),
+                    Instruction::F64Const(10.0),
+                    Instruction::F64Div,
+                    Instruction::F64Floor,
+                    Instruction::LocalTee(3),
+                    Instruction::F64Const(1.0),
+                    Instruction::F64Ge,
+                    Instruction::BrIf(0),
+                    Instruction::End,
+                    //the number of zeros, and 10 to that power
+                    Instruction::LocalGet(1),
+                    Instruction::I32Const(17),
+                    Instruction::I32Sub,
+                    Instruction::LocalSet(1),
+                    Instruction::F64Const(1.0),
+                    Instruction::LocalSet(4),
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(1),
+                    Instruction::I32GeS,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(4),
+                    Instruction::F64Const(10.0),
+                    Instruction::F64Mul,
+                    Instruction::LocalSet(4),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(2),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(4),
+                    Instruction::F64Div,
+                    Instruction::F64Nearest,
+                    Instruction::I64TruncSatF64S,
+                    Instruction::Call(int_to_str),
+                    Instruction::I64Const(0),
+                    Instruction::LocalGet(1),
+                    Instruction::Call(digits),
+                    Instruction::Call(concat),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::NewArray => {
+                //params: length, element size, locals: array
+                //the heap is never freed, so new memory is already zeroed
+                let alloc = comp.helper(Helper::Alloc);
+                let mut f = Function::new(vec![(1, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Const(0),
+                    Instruction::I32LtS,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::Unreachable, //negative length
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Mul,
+                    Instruction::I32Const(8),
+                    Instruction::I32Add,
+                    Instruction::Call(alloc),
+                    Instruction::LocalTee(2),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Store(mem(2)),
+                    Instruction::LocalGet(2),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::ArrayIndex => {
+                //params: array, index, element size
+                let mut f = Function::new(vec![]);
+                emit(&mut f, &[
+                    //unsigned compare, so negative indexes are out of bounds too
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32GeU,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::Unreachable, //index out of bounds
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Const(8),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Mul,
+                    Instruction::I32Add,
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::NewList => {
+                //params: element size, locals: list
+                let alloc = comp.helper(Helper::Alloc);
+                let mut f = Function::new(vec![(1, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::I32Const(12),
+                    Instruction::Call(alloc),
+                    Instruction::LocalTee(1),
+                    Instruction::I32Const(LIST_CAPACITY),
+                    Instruction::I32Store(field(4)),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Const(LIST_CAPACITY),
+                    Instruction::I32Mul,
+                    Instruction::Call(alloc),
+                    Instruction::I32Store(field(8)),
+                    Instruction::LocalGet(1),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::ListIndex => {
+                //params: list, index, element size
+                let mut f = Function::new(vec![]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32GeU,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::Unreachable, //index out of bounds
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(8)),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Mul,
+                    Instruction::I32Add,
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::ListPush => {
+                //params: list, element size, locals: new elements
+                let alloc = comp.helper(Helper::Alloc);
+                let mut f = Function::new(vec![(1, ValType::I32)]);
+                emit(&mut f, &[
+                    //a list global is 0 until main creates it, pushing to it would overwrite the static data
+                    Instruction::LocalGet(0),
+                    Instruction::I32Eqz,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::Unreachable,
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(4)),
+                    Instruction::I32Eq,
+                    Instruction::If(BlockType::Empty),
+                    //full, double the capacity and move the elements
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(4)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Mul,
+                    Instruction::I32Const(2),
+                    Instruction::I32Mul,
+                    Instruction::Call(alloc),
+                    Instruction::LocalTee(2),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(8)),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Mul,
+                    Instruction::MemoryCopy {src_mem: 0, dst_mem: 0},
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Store(field(8)),
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(4)),
+                    Instruction::I32Const(2),
+                    Instruction::I32Mul,
+                    Instruction::I32Store(field(4)),
+                    Instruction::End,
+                    //address of the new element
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(8)),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Mul,
+                    Instruction::I32Add,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::I32Store(mem(2)),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::ListPop => {
+                //params: list, element size
+                let mut f = Function::new(vec![]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32Eqz,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::Unreachable, //pop from an empty list
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32Const(1),
+                    Instruction::I32Sub,
+                    Instruction::I32Store(mem(2)),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(field(8)),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Mul,
+                    Instruction::I32Add,
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::StrCompare => {
+                //params: a, b, locals: index, shorter length, byte of a, byte of b
+                let mut f = Function::new(vec![(4, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Eq,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::I32Const(0), //the same string
+                    Instruction::Return,
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32LtU,
+                    Instruction::Select,
+                    Instruction::LocalSet(3),
+                    //the first different byte decides
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::I32GeU,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Add,
+                    Instruction::I32Load8U(byte(4)),
+                    Instruction::LocalTee(4),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Add,
+                    Instruction::I32Load8U(byte(4)),
+                    Instruction::LocalTee(5),
+                    Instruction::I32Ne,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::LocalGet(4),
+                    Instruction::LocalGet(5),
+                    Instruction::I32Sub,
+                    Instruction::Return,
+                    Instruction::End,
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(2),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    //one starts with the other, the shorter one comes first
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32Sub,
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::Substr => {
+                //params: string, start, length, locals: remaining length, new string
+                let alloc = comp.helper(Helper::Alloc);
+                let mut f = Function::new(vec![(2, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalSet(3),
+                    //start = min(max(start, 0), length of the string)
+                    Instruction::I32Const(0),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Const(0),
+                    Instruction::I32LtS,
+                    Instruction::Select,
+                    Instruction::LocalSet(1),
+                    Instruction::LocalGet(3),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(3),
+                    Instruction::I32GtS,
+                    Instruction::Select,
+                    Instruction::LocalSet(1),
+                    //length = min(max(length, 0), what is left after start)
+                    Instruction::LocalGet(3),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Sub,
+                    Instruction::LocalSet(3),
+                    Instruction::I32Const(0),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(0),
+                    Instruction::I32LtS,
+                    Instruction::Select,
+                    Instruction::LocalSet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::I32GtS,
+                    Instruction::Select,
+                    Instruction::LocalSet(2),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::Call(alloc),
+                    Instruction::LocalTee(4),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Store(mem(2)),
+                    Instruction::LocalGet(4),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(0),
+                    Instruction::I32Const(4),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(1),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(2),
+                    Instruction::MemoryCopy {src_mem: 0, dst_mem: 0},
+                    Instruction::LocalGet(4),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::Find => {
+                //params: string, needle, from, locals: last index the needle fits at, index in the needle
+                let mut f = Function::new(vec![(2, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::I32Const(0),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(0),
+                    Instruction::I32LtS,
+                    Instruction::Select,
+                    Instruction::LocalSet(2),
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32Sub,
+                    Instruction::LocalSet(3),
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(3),
+                    Instruction::I32GtS,
+                    Instruction::BrIf(1),
+                    //compare the needle with the bytes at from
+                    Instruction::I32Const(0),
+                    Instruction::LocalSet(4),
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(4),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::I32GeU,
+                    Instruction::If(BlockType::Empty),
+                    Instruction::LocalGet(2), //every byte matched
+                    Instruction::Return,
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(4),
+                    Instruction::I32Add,
+                    Instruction::I32Load8U(byte(4)),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(4),
+                    Instruction::I32Add,
+                    Instruction::I32Load8U(byte(4)),
+                    Instruction::I32Ne,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(4),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(4),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(2),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::I32Const(-1),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::ToUpper => change_case(comp, b'a'),
+            Helper::ToLower => change_case(comp, b'A'),
+            Helper::Trim => {
+                //params: string, locals: start, end
+                let substr = comp.helper(Helper::Substr);
+                let mut f = Function::new(vec![(2, ValType::I32)]);
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalSet(2),
+                    //skip the bytes up to ' ' at the start
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I32GeU,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Add,
+                    Instruction::I32Load8U(byte(4)),
+                    Instruction::I32Const(32),
+                    Instruction::I32GtU,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(1),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    //and at the end, the byte before end is at offset 3
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(1),
+                    Instruction::I32LeU,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Add,
+                    Instruction::I32Load8U(byte(3)),
+                    Instruction::I32Const(32),
+                    Instruction::I32GtU,
+                    Instruction::BrIf(1),
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(1),
+                    Instruction::I32Sub,
+                    Instruction::LocalSet(2),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(2),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Sub,
+                    Instruction::Call(substr),
+                    Instruction::End,
+                ]);
+                f
+            }
+            Helper::Split => {
+                //params: string, separator, locals: list, start of the current part, end of it, separator length, string length
+                let new_list = comp.helper(Helper::NewList);
+                let list_push = comp.helper(Helper::ListPush);
+                let substr = comp.helper(Helper::Substr);
+                let find = comp.helper(Helper::Find);
+                let mut f = Function::new(vec![(5, ValType::I32)]);
+                let push_part = [
+                    //list.push(substr(string, start, end - start))
+                    Instruction::LocalGet(2),
+                    Instruction::I32Const(4),
+                    Instruction::Call(list_push),
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(3),
+                    Instruction::LocalGet(4),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Sub,
+                    Instruction::Call(substr),
+                    Instruction::I32Store(mem(2)),
+                ];
+                emit(&mut f, &[
+                    Instruction::LocalGet(0),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalSet(6),
+                    Instruction::LocalGet(1),
+                    Instruction::I32Load(mem(2)),
+                    Instruction::LocalSet(5),
+                    Instruction::I32Const(4),
+                    Instruction::Call(new_list),
+                    Instruction::LocalSet(2),
+                    Instruction::Block(BlockType::Empty),
+                    Instruction::Loop(BlockType::Empty),
+                    Instruction::LocalGet(5),
+                    Instruction::I32Eqz,
+                    Instruction::If(BlockType::Result(ValType::I32)),
+                    //an empty separator ends a part after every byte but the last one
+                    Instruction::LocalGet(3),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::I32Const(-1),
+                    Instruction::LocalGet(3),
+                    Instruction::I32Const(1),
+                    Instruction::I32Add,
+                    Instruction::LocalGet(6),
+                    Instruction::I32LtS,
+                    Instruction::Select,
+                    Instruction::Else,
+                    Instruction::LocalGet(0),
+                    Instruction::LocalGet(1),
+                    Instruction::LocalGet(3),
+                    Instruction::Call(find),
+                    Instruction::End,
+                    Instruction::LocalTee(4),
+                    Instruction::I32Const(0),
+                    Instruction::I32LtS,
+                    Instruction::BrIf(1),
+                ]);
+                emit(&mut f, &push_part);
+                emit(&mut f, &[
+                    Instruction::LocalGet(4),
+                    Instruction::LocalGet(5),
+                    Instruction::I32Add,
+                    Instruction::LocalSet(3),
+                    Instruction::Br(0),
+                    Instruction::End,
+                    Instruction::End,
+                    //the rest of the string is the last part
+                    Instruction::LocalGet(6),
+                    Instruction::LocalSet(4),
+                ]);
+                emit(&mut f, &push_part);
+                emit(&mut f, &[
+                    Instruction::LocalGet(2),
+                    Instruction::End,
+                ]);
+                f
+            }
+        }
+    }
+}
+
+fn change_case(comp: &mut Compiler, first: u8) -> Function {
+    //params: string, locals: new string, index, length, byte
+    //flipping bit 5 turns an ascii letter from the range starting at first into the other case
+    let alloc = comp.helper(Helper::Alloc);
+    let mut f = Function::new(vec![(4, ValType::I32)]);
+    emit(&mut f, &[
+        Instruction::LocalGet(0),
+        Instruction::I32Load(mem(2)),
+        Instruction::LocalTee(3),
+        Instruction::I32Const(4),
+        Instruction::I32Add,
+        Instruction::Call(alloc),
+        Instruction::LocalTee(1),
+        Instruction::LocalGet(3),
+        Instruction::I32Store(mem(2)),
+        Instruction::Block(BlockType::Empty),
+        Instruction::Loop(BlockType::Empty),
+        Instruction::LocalGet(2),
+        Instruction::LocalGet(3),
+        Instruction::I32GeU,
+        Instruction::BrIf(1),
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(2),
+        Instruction::I32Add,
+        Instruction::LocalGet(0),
+        Instruction::LocalGet(2),
+        Instruction::I32Add,
+        Instruction::I32Load8U(byte(4)),
+        Instruction::LocalTee(4),
+        Instruction::LocalGet(4),
+        Instruction::I32Const(first as i32),
+        Instruction::I32Sub,
+        Instruction::I32Const(26),
+        Instruction::I32LtU,
+        Instruction::I32Const(5),
+        Instruction::I32Shl,
+        Instruction::I32Xor,
+        Instruction::I32Store8(byte(4)),
+        Instruction::LocalGet(2),
+        Instruction::I32Const(1),
+        Instruction::I32Add,
+        Instruction::LocalSet(2),
+        Instruction::Br(0),
+        Instruction::End,
+        Instruction::End,
+        Instruction::LocalGet(1),
+        Instruction::End,
+    ]);
+    f
+}
+
+pub fn mem(align: u32) -> MemArg {
+    MemArg {
+        offset: 0,
+        align,
+        memory_index: 0,
+    }
+}
+
+fn field(offset: u64) -> MemArg {
+    //an i32 field of a list header
+    MemArg {
+        offset,
+        align: 2,
+        memory_index: 0,
+    }
+}
+
+fn byte(offset: u64) -> MemArg {
+    //a byte of a string, the bytes start at offset 4
+    MemArg {
+        offset,
+        align: 0,
+        memory_index: 0,
+    }
+}
+
+fn emit(f: &mut Function, instructions: &[Instruction]) {
+    for i in instructions {
+        f.instruction(i);
+    }
+}
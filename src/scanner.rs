@@ -3,7 +3,6 @@ use std::iter::Peekable;
 use std::str::Chars;
 
 pub struct Scanner<'a> {
-    pub source: &'a str,
     pub chars: Peekable<Chars<'a>>,
     pub line: usize,
     pub tokens: Vec<Token>,
@@ -14,7 +13,6 @@ pub struct Scanner<'a> {
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str, filename: &'a str) -> Scanner<'a> {
         Scanner {
-            source,
             chars: source.chars().peekable(),
             line: 1,
             tokens: Vec::new(),
@@ -31,11 +29,16 @@ impl<'a> Scanner<'a> {
                 '{' => self.make_token(TokenType::LeftBrace, None),
                 '}' => self.make_token(TokenType::RightBrace, None),
                 ',' => self.make_token(TokenType::Comma, None),
+                ':' => self.make_token(TokenType::Colon, None),
+                '?' => self.make_token(TokenType::Question, None),
                 '.' => self.make_token(TokenType::Dot, None),
                 '-' => {
                     if let Some(&'=') = self.chars.peek() {
                         self.chars.next();
                         self.make_token(TokenType::MinusEqual, None);
+                    } else if let Some(&'>') = self.chars.peek() {
+                        self.chars.next();
+                        self.make_token(TokenType::ThinArrow, None);
                     } else {
                         self.make_token(TokenType::Minus, None);
                     }
@@ -87,6 +90,9 @@ impl<'a> Scanner<'a> {
                     if let Some(&'=') = self.chars.peek() {
                         self.chars.next();
                         self.make_token(TokenType::EqualEqual, None);
+                    } else if let Some(&'>') = self.chars.peek() {
+                        self.chars.next();
+                        self.make_token(TokenType::Arrow, None);
                     } else {
                         self.make_token(TokenType::Equal, None);
                     }
@@ -95,6 +101,9 @@ impl<'a> Scanner<'a> {
                     if let Some(&'=') = self.chars.peek() {
                         self.chars.next();
                         self.make_token(TokenType::LessEqual, None);
+                    } else if let Some(&'<') = self.chars.peek() {
+                        self.chars.next();
+                        self.with_equal(TokenType::ShiftLeft, TokenType::ShiftLeftEqual);
                     } else {
                         self.make_token(TokenType::Less, None);
                     }
@@ -103,6 +112,14 @@ impl<'a> Scanner<'a> {
                     if let Some(&'=') = self.chars.peek() {
                         self.chars.next();
                         self.make_token(TokenType::GreaterEqual, None);
+                    } else if let Some(&'>') = self.chars.peek() {
+                        self.chars.next();
+                        if let Some(&'>') = self.chars.peek() {
+                            self.chars.next();
+                            self.with_equal(TokenType::ShiftRightUnsigned, TokenType::ShiftRightUnsignedEqual);
+                        } else {
+                            self.with_equal(TokenType::ShiftRight, TokenType::ShiftRightEqual);
+                        }
                     } else {
                         self.make_token(TokenType::Greater, None);
                     }
@@ -112,7 +129,7 @@ impl<'a> Scanner<'a> {
                         self.chars.next();
                         self.make_token(TokenType::And, None);
                     } else {
-                        self.error("expected & and & for and operator");
+                        self.with_equal(TokenType::BitAnd, TokenType::BitAndEqual);
                     }
                 }
                 '|' => {
@@ -120,9 +137,11 @@ impl<'a> Scanner<'a> {
                         self.chars.next();
                         self.make_token(TokenType::Or, None);
                     } else {
-                        self.error("expected | after | for or operator");
+                        self.with_equal(TokenType::BitOr, TokenType::BitOrEqual);
                     }
                 }
+                '^' => self.with_equal(TokenType::BitXor, TokenType::BitXorEqual),
+                '~' => self.make_token(TokenType::BitNot, None),
                 '"' => self.string(),
                 ' ' => (),
                 '\r' => (),
@@ -131,7 +150,7 @@ impl<'a> Scanner<'a> {
                 _ => {
                     if ch.is_ascii_digit() {
                         self.number(ch);
-                    } else if ch.is_ascii_alphabetic() {
+                    } else if ch.is_ascii_alphabetic() || ch == '_' {
                         self.identifier(ch);
                     } else {
                         self.error(format!("unknown character {}", ch).as_str());
@@ -184,15 +203,46 @@ impl<'a> Scanner<'a> {
                 }
             }
             self.make_token(TokenType::Value, Some(Value::Float(number.parse::<f64>().unwrap())));
+        } else if let Some(&'u') = self.chars.peek() {
+            //5u is a uint literal
+            self.chars.next();
+            match number.parse::<u32>() {
+                Ok(n) => self.make_token(TokenType::Value, Some(Value::Uint(n))),
+                Err(_) => self.error(format!("uint literal {} is too large", number).as_str()),
+            }
+        } else if let Some(&'L') = self.chars.peek() {
+            //5L is a long literal
+            self.chars.next();
+            match number.parse::<i64>() {
+                Ok(n) => self.make_token(TokenType::Value, Some(Value::Long(n))),
+                Err(_) => self.error(format!("long literal {} is too large", number).as_str()),
+            }
+        } else if let Ok(n) = number.parse::<i32>() {
+            self.make_token(TokenType::Value, Some(Value::Int(n)));
+        } else if number == "2147483648" && self.negates_next() {
+            //-2147483648 is the smallest int, the literal without its sign does not fit in an int
+            self.tokens.pop();
+            self.make_token(TokenType::Value, Some(Value::Int(i32::MIN)));
         } else {
-            self.make_token(TokenType::Value, Some(Value::Int(number.parse::<i32>().unwrap())));
+            //too large for an int
+            match number.parse::<i64>() {
+                Ok(n) => self.make_token(TokenType::Value, Some(Value::Long(n))),
+                Err(_) => self.error(format!("integer literal {} is too large", number).as_str()),
+            }
         }
     }
 
+    fn negates_next(&self) -> bool {
+        //the last token is a unary minus: a "-" that does not follow a value, a name or a closing bracket
+        let n = self.tokens.len();
+        n > 0 && self.tokens[n - 1].tt == TokenType::Minus
+            && (n == 1 || !matches!(self.tokens[n - 2].tt, TokenType::Value | TokenType::Identifier | TokenType::RightParen | TokenType::RightSquare))
+    }
+
     fn identifier(&mut self, first: char) {
         let mut identifier = String::from(first);
         while let Some(&ch) = self.chars.peek() {
-            if ch.is_ascii_alphanumeric() {
+            if ch.is_ascii_alphanumeric() || ch == '_' {
                 identifier.push(ch);
                 self.chars.next();
             } else {
@@ -208,15 +258,42 @@ impl<'a> Scanner<'a> {
             "true" => self.make_token(TokenType::Value, Some(Value::Bool(true))),
             "false" => self.make_token(TokenType::Value, Some(Value::Bool(false))),
             "int" => self.make_token(TokenType::Int, None),
+            "uint" => self.make_token(TokenType::Uint, None),
+            "long" => self.make_token(TokenType::Long, None),
             "float" => self.make_token(TokenType::Float, None),
             "string" => self.make_token(TokenType::String, None),
+            "list" => self.make_token(TokenType::List, None),
             "bool" => self.make_token(TokenType::Bool, None),
             "void" => self.make_token(TokenType::Void, None),
             "break" => self.make_token(TokenType::Break, None),
+            "continue" => self.make_token(TokenType::Continue, None),
+            "for" => self.make_token(TokenType::For, None),
+            "struct" => self.make_token(TokenType::Struct, None),
+            "enum" => self.make_token(TokenType::Enum, None),
+            "match" => self.make_token(TokenType::Match, None),
+            "switch" => self.make_token(TokenType::Switch, None),
+            "global" => self.make_token(TokenType::Global, None),
+            "export" => self.make_token(TokenType::Export, None),
+            "const" => self.make_token(TokenType::Const, None),
+            "extern" => self.make_token(TokenType::Extern, None),
+            "import" => self.make_token(TokenType::Import, None),
+            "fn" => self.make_token(TokenType::Fn, None),
+            "case" => self.make_token(TokenType::Case, None),
+            "default" => self.make_token(TokenType::Default, None),
             _ => self.make_token(TokenType::Identifier, Some(Value::String(identifier))),
         }
     }
 
+    fn with_equal(&mut self, tt: TokenType, tt_equal: TokenType) {
+        //an operator that has a compound assignment version, like << and <<=
+        if let Some(&'=') = self.chars.peek() {
+            self.chars.next();
+            self.make_token(tt_equal, None);
+        } else {
+            self.make_token(tt, None);
+        }
+    }
+
     fn make_token(&mut self, tt: TokenType, literal: Option<Value>) {
         self.tokens.push(Token {
             tt,
@@ -242,8 +319,9 @@ pub enum Value {
     String(String),
     Float(f64),
     Int(i32),
+    Uint(u32),
+    Long(i64),
     Bool(bool),
-    Index(i32),
 }
 
 impl Value {
@@ -259,7 +337,8 @@ impl Value {
             }
             Value::Float(float) => float.to_string(),
             Value::Int(int) => int.to_string(),
-            _ => {KeyScriptError::error(KeyScriptError::ScannerError, Some("cannot convert value to string"), None, None); std::process::exit(0);},
+            Value::Uint(uint) => uint.to_string(),
+            Value::Long(long) => long.to_string(),
         }
     }
 }
@@ -278,6 +357,8 @@ pub enum TokenType {
     LeftBrace,
     RightBrace,
     Comma,
+    Colon,
+    Question,
     Dot,
     Minus,
     MinusEqual,
@@ -296,24 +377,56 @@ pub enum TokenType {
     BangEqual,
     Equal,
     EqualEqual,
+    Arrow,
+    ThinArrow,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
     And,
     Or,
+    BitAnd,
+    BitAndEqual,
+    BitOr,
+    BitOrEqual,
+    BitXor,
+    BitXorEqual,
+    BitNot,
+    ShiftLeft,
+    ShiftLeftEqual,
+    ShiftRight,
+    ShiftRightEqual,
+    ShiftRightUnsigned,
+    ShiftRightUnsignedEqual,
 
     Identifier,
     String,
+    List,
+    Fn,
     Int,
+    Uint,
+    Long,
     Float,
     Bool,
     Void,
     Break,
+    Continue,
     Value,
     If,
     Else,
     While,
+    For,
+    Struct,
+    Enum,
+    Match,
+    Switch,
+    Case,
+    Default,
+    Global,
+    Export,
+    Const,
+    Extern,
+    Import,
     Print,
     Return,
     Eof,